@@ -1,6 +1,6 @@
 <?xml version="1.0" encoding="UTF-8"?>
 <tileset version="1.5" tiledversion="1.7.2" name="Reference Tiles" tilewidth="16" tileheight="16" tilecount="64" columns="8">
- <transformations hflip="0" vflip="0" rotate="0" preferuntransformed="1"/>
+ <transformations hflip="1" vflip="1" rotate="1" preferuntransformed="1"/>
  <image source="../tileset.png" width="128" height="128"/>
  <tile id="0" type="empty"/>
  <tile id="1" type="solid_top_left"/>
@@ -32,6 +32,7 @@
  <tile id="28" type="solid_tile"/>
  <tile id="29" type="slope_double_up_bottom"/>
  <tile id="30" type="slope_double_down_bottom"/>
+ <tile id="31" type="solid"/>
  <tile id="32" type="spikes_left"/>
  <tile id="33" type="solid_top_fade_left"/>
  <tile id="34" type="solid_top_fade_right"/>
@@ -1,23 +1,34 @@
 //! Asset loading and bundling.
 
+#[cfg(feature = "game")]
 use std::cell::RefCell;
+#[cfg(feature = "game")]
 use std::collections::HashMap;
+#[cfg(feature = "game")]
 use std::ops::{Index, IndexMut};
 use std::str::FromStr;
 
 use serde::de::IntoDeserializer;
 use serde::Deserialize;
+#[cfg(feature = "game")]
 use tetra::graphics::text::{Text, VectorFontBuilder};
-use tetra::graphics::{Color, DrawParams, FilterMode, Texture};
+#[cfg(feature = "game")]
+use tetra::graphics::{Color, DrawParams, FilterMode};
+#[cfg(feature = "game")]
 use tetra::Context;
 
+#[cfg(feature = "game")]
 use crate::common::{asset_path, vector};
+#[cfg(feature = "game")]
 use crate::resources::Resources;
+#[cfg(feature = "game")]
 use crate::tiled::TextHAlign;
 
 /// A namespace for colors that are remappable to various colors in the palette.
+#[cfg(feature = "game")]
 pub struct RemappableColors;
 
+#[cfg(feature = "game")]
 impl RemappableColors {
    pub const BACKGROUND: Color = Color {
       r: 0.0,
@@ -39,22 +50,14 @@ impl RemappableColors {
    };
 }
 
-/// A texture resource consisting of a single white pixel.
-pub struct WhiteTexture(pub Texture);
-
-impl WhiteTexture {
-   pub fn insert_to(ctx: &mut Context, resources: &mut Resources) -> anyhow::Result<()> {
-      let texture = Texture::from_rgba(ctx, 1, 1, &[255, 255, 255, 255])?;
-      Ok(resources.insert::<WhiteTexture>(WhiteTexture(texture)))
-   }
-}
-
 /// A storage for a single size of a font.
+#[cfg(feature = "game")]
 pub struct FontSize {
    size: f32,
    text: RefCell<Text>,
 }
 
+#[cfg(feature = "game")]
 impl FontSize {
    const SCALE: f32 = 6.0;
 
@@ -110,11 +113,13 @@ impl FontSize {
 }
 
 /// A store for fonts of different sizes.
+#[cfg(feature = "game")]
 pub struct FontSizes {
    builder: VectorFontBuilder,
    sizes: HashMap<u32, FontSize>,
 }
 
+#[cfg(feature = "game")]
 impl FontSizes {
    /// Loads the font with the given size, if not already loaded.
    pub fn load(&mut self, ctx: &mut Context, size: u32) -> anyhow::Result<&FontSize> {
@@ -134,10 +139,12 @@ impl FontSizes {
 }
 
 /// A resource consisting of all the different fonts used in the game.
+#[cfg(feature = "game")]
 pub struct Fonts {
    pub regular: FontSizes,
 }
 
+#[cfg(feature = "game")]
 impl Fonts {
    pub fn load_to(resources: &mut Resources) -> anyhow::Result<()> {
       let regular = FontSizes {
@@ -164,6 +171,7 @@ impl FromStr for FontFamily {
    }
 }
 
+#[cfg(feature = "game")]
 impl Index<FontFamily> for Fonts {
    type Output = FontSizes;
 
@@ -174,6 +182,7 @@ impl Index<FontFamily> for Fonts {
    }
 }
 
+#[cfg(feature = "game")]
 impl IndexMut<FontFamily> for Fonts {
    fn index_mut(&mut self, index: FontFamily) -> &mut Self::Output {
       match index {
@@ -0,0 +1,83 @@
+//! The asset compiler. Compiles the resources in `res/` into the assets the game loads from
+//! `assets/generated/`, checking them for problems along the way.
+//!
+//! Outputs are only rebuilt when their inputs have changed since they were last built; pass
+//! `--force` to rebuild everything anyway. Problems found in the resources are listed; if any of
+//! them are errors, the compiler exits with a non-zero exit code.
+
+use std::path::Path;
+
+use anyhow::Context;
+use log::{error, info};
+use simple_logger::SimpleLogger;
+
+use memphlus::common::asset_path;
+use memphlus::map::{CompiledMap, Map};
+
+/// Compiles the map, unless the compiled map is up to date. The map is checked for problems
+/// before it's compiled, and isn't compiled if any of them are errors.
+fn compile_map(source: &Path, output: &Path, force: bool) -> anyhow::Result<()> {
+   if !force && is_up_to_date(source, output) {
+      info!("{:?} is up to date", output);
+      return Ok(());
+   }
+
+   info!("compiling {:?} into {:?}", source, output);
+   let diagnostics = Map::check(source)?;
+   for diagnostic in &diagnostics {
+      diagnostic.log();
+   }
+   let errors = diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count();
+   if errors > 0 {
+      anyhow::bail!("{:?}: found {} error(s)", source, errors);
+   }
+   CompiledMap::compile(source)?.write(output)
+}
+
+/// Checks whether the compiled map at `output` was compiled from `source`, and none of the files
+/// it was compiled from have changed since.
+fn is_up_to_date(source: &Path, output: &Path) -> bool {
+   if !output.is_file() {
+      return false;
+   }
+   match CompiledMap::read(output) {
+      Ok(map) => {
+         map.sources().first().map(|path| path.as_path()) == Some(source)
+            && map.sources_changed() == Some(false)
+      }
+      Err(error) => {
+         info!("{:#}; rebuilding it", error);
+         false
+      }
+   }
+}
+
+/// Compiles all assets.
+fn compile_assets(force: bool) -> anyhow::Result<()> {
+   let generated = asset_path("generated");
+   std::fs::create_dir_all(&generated).with_context(|| format!("cannot create {:?}", generated))?;
+   compile_map(&Map::source_path(), &Map::compiled_path(), force)
+}
+
+fn main() {
+   SimpleLogger::new().without_timestamps().init().unwrap();
+
+   let mut force = false;
+   for arg in std::env::args().skip(1) {
+      match arg.as_str() {
+         "--force" => force = true,
+         _ => {
+            error!(
+               "unknown argument {:?} (usage: memphlus-assets [--force])",
+               arg
+            );
+            std::process::exit(2);
+         }
+      }
+   }
+
+   if let Err(error) = compile_assets(force) {
+      error!("{:#}", error);
+      std::process::exit(1);
+   }
+}
@@ -3,16 +3,25 @@
 use std::path::{Path, PathBuf};
 
 use log::debug;
+#[cfg(feature = "game")]
 use tetra::graphics::mesh::Vertex;
+#[cfg(feature = "game")]
 use tetra::graphics::{Color, Rectangle};
-use tetra::math::Vec2;
+#[cfg(feature = "game")]
 use tetra::{window, Context};
+use vek::Vec2;
 
 /// Creates a 2D vector.
 pub fn vector(x: f32, y: f32) -> Vec2<f32> {
    Vec2::new(x, y)
 }
 
+/// Returns the Z component of the cross product of two 2D vectors. In a Y-up coordinate system,
+/// this is positive if `b` points counterclockwise from `a`.
+pub fn cross(a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+   a.x * b.y - a.y * b.x
+}
+
 pub trait ToVekVec2 {
    fn vek(self) -> Vec2<f32>;
 }
@@ -40,7 +49,92 @@ pub fn stretch_squish(size: Vec2<f32>, aspect: f32) -> Vec2<f32> {
    vector(size.y * aspect, size.x / aspect)
 }
 
-pub type Rect = Rectangle<f32>;
+/// An axis-aligned rectangle. This mirrors tetra's `Rectangle`, so that the code that doesn't
+/// render anything can be built without tetra.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+   pub x: f32,
+   pub y: f32,
+   pub width: f32,
+   pub height: f32,
+}
+
+impl Rect {
+   pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+      Self {
+         x,
+         y,
+         width,
+         height,
+      }
+   }
+
+   /// Returns whether the two rectangles overlap. Rectangles that only touch don't overlap.
+   pub fn intersects(&self, other: &Rect) -> bool {
+      self.x < other.right()
+         && self.right() > other.x
+         && self.y < other.bottom()
+         && self.bottom() > other.y
+   }
+
+   /// Returns whether the point is inside the rectangle. The right and bottom edges are not part
+   /// of the rectangle.
+   pub fn contains_point(&self, point: Vec2<f32>) -> bool {
+      self.x <= point.x && point.x < self.right() && self.y <= point.y && point.y < self.bottom()
+   }
+
+   /// Returns the smallest rectangle containing both rectangles.
+   pub fn combine(&self, other: &Rect) -> Rect {
+      let x = self.x.min(other.x);
+      let y = self.y.min(other.y);
+      let right = self.right().max(other.right());
+      let bottom = self.bottom().max(other.bottom());
+      Rect::new(x, y, right - x, bottom - y)
+   }
+
+   pub fn left(&self) -> f32 {
+      self.x
+   }
+
+   pub fn right(&self) -> f32 {
+      self.x + self.width
+   }
+
+   pub fn top(&self) -> f32 {
+      self.y
+   }
+
+   pub fn bottom(&self) -> f32 {
+      self.y + self.height
+   }
+
+   pub fn center(&self) -> Vec2<f32> {
+      vector(self.x + self.width / 2.0, self.y + self.height / 2.0)
+   }
+
+   pub fn top_left(&self) -> Vec2<f32> {
+      vector(self.left(), self.top())
+   }
+
+   pub fn top_right(&self) -> Vec2<f32> {
+      vector(self.right(), self.top())
+   }
+
+   pub fn bottom_left(&self) -> Vec2<f32> {
+      vector(self.left(), self.bottom())
+   }
+
+   pub fn bottom_right(&self) -> Vec2<f32> {
+      vector(self.right(), self.bottom())
+   }
+}
+
+#[cfg(feature = "game")]
+impl From<Rect> for Rectangle<f32> {
+   fn from(rect: Rect) -> Self {
+      Rectangle::new(rect.x, rect.y, rect.width, rect.height)
+   }
+}
 
 /// Creates a rectangle from a point and a size.
 pub fn rect(point: Vec2<f32>, size: Vec2<f32>) -> Rect {
@@ -48,6 +142,7 @@ pub fn rect(point: Vec2<f32>, size: Vec2<f32>) -> Rect {
 }
 
 /// Creates a colored vertex with the UV coordinates set to `(0.0, 0.0)`.
+#[cfg(feature = "game")]
 pub fn colored_vertex(position: Vec2<f32>, color: Color) -> Vertex {
    Vertex {
       position,
@@ -62,24 +157,23 @@ pub trait RectVectors<T> {
    fn size(&self) -> Vec2<T>;
 }
 
-impl<T> RectVectors<T> for Rectangle<T>
-where
-   T: Copy,
-{
-   fn position(&self) -> Vec2<T> {
+impl RectVectors<f32> for Rect {
+   fn position(&self) -> Vec2<f32> {
       Vec2::new(self.x, self.y)
    }
 
-   fn size(&self) -> Vec2<T> {
+   fn size(&self) -> Vec2<f32> {
       Vec2::new(self.width, self.height)
    }
 }
 
 /// Extra color operations.
+#[cfg(feature = "game")]
 pub trait ColorOps {
    fn with_alpha(&self, a: f32) -> Self;
 }
 
+#[cfg(feature = "game")]
 impl ColorOps for Color {
    fn with_alpha(&self, a: f32) -> Self {
       Self { a, ..*self }
@@ -111,6 +205,7 @@ pub fn load_asset_to_string(path: impl AsRef<Path>) -> anyhow::Result<String> {
    Ok(std::fs::read_to_string(path)?)
 }
 
+#[cfg(feature = "game")]
 pub fn window_size(ctx: &Context) -> Vec2<f32> {
    let (width, height) = window::get_size(ctx);
    vector(width as f32, height as f32)
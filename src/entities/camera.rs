@@ -2,10 +2,13 @@
 
 use hecs::{Entity, World};
 use rapier2d::prelude::{Ball, ColliderBuilder, InteractionGroups};
+#[cfg(feature = "game")]
 use tetra::Context;
 use vek::Vec2;
 
-use crate::common::{rect, vector, Rect, ToNalgebraVector2};
+#[cfg(feature = "game")]
+use crate::common::rect;
+use crate::common::{vector, Rect, ToNalgebraVector2};
 use crate::interpolation::Interpolated;
 use crate::physics::{CollisionGroups, Physics};
 
@@ -131,6 +134,7 @@ impl Camera {
    }
 
    /// Linearly interpolates the camera's position and size.
+   #[cfg(feature = "game")]
    pub fn blend(&self, ctx: &Context) -> Rect {
       let position = self.position.blend(ctx);
       let size = self.size.blend(ctx);
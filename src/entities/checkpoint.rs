@@ -1,10 +1,11 @@
 //! Externally triggered checkpoints.
 
 use hecs::{Entity, World};
+use log::debug;
 use vek::Vec2;
 
 use super::trigger::Triggered;
-use super::Position;
+use super::{Name, Position};
 
 /// A respawn position. If an entity triggers a checkpoint and has the `RespawnPosition` component,
 /// that position will be set to the checkpoint's position.
@@ -31,6 +32,9 @@ impl Checkpoint {
             let RespawnPosition(respawn_position) = &mut *world.get_mut(entity).unwrap();
             *respawn_position = position;
          }
+         if let Ok(Name(name)) = world.get::<Name>(checkpoint).as_deref() {
+            debug!("respawn position set by checkpoint {:?}", name);
+         }
          let _ = world.remove_one::<Triggered>(checkpoint);
       }
    }
@@ -1,21 +1,33 @@
 //! Static colliders.
 
 use hecs::{Entity, World};
-use rapier2d::prelude::{ColliderBuilder, InteractionGroups};
+use rapier2d::prelude::InteractionGroups;
+use vek::Vec2;
 
-use crate::common::{Rect, ToNalgebraVector2};
+use crate::common::ToNalgebraVector2;
 use crate::physics::{CollisionGroups, Physics};
 
 use super::physics::Collider;
+use super::shapes::Shape;
+use super::{Position, Rotation};
 
-/// A rectangular collider.
-pub struct RectCollider;
+/// A static, solid collider.
+pub struct StaticCollider;
 
-impl RectCollider {
-   /// Spawns a new rectangular collider into the world.
-   pub fn spawn(world: &mut World, physics: &mut Physics, entity: Entity, rect: Rect) {
-      let collider = ColliderBuilder::cuboid(rect.width / 2.0, rect.height / 2.0)
-         .translation(rect.center().nalgebra())
+impl StaticCollider {
+   /// Spawns a new static collider with the given shape into the world.
+   pub fn spawn(
+      world: &mut World,
+      physics: &mut Physics,
+      entity: Entity,
+      position: Vec2<f32>,
+      rotation: f32,
+      shape: Shape,
+   ) -> anyhow::Result<()> {
+      let collider = shape
+         .collider()?
+         .translation(position.nalgebra())
+         .rotation(rotation)
          .collision_groups(InteractionGroups::new(
             CollisionGroups::SOLIDS,
             CollisionGroups::ALL,
@@ -23,6 +35,16 @@ impl RectCollider {
          .build();
       let collider = physics.colliders.insert(collider);
 
-      world.spawn_at(entity, (RectCollider, Collider(collider)));
+      world.spawn_at(
+         entity,
+         (
+            StaticCollider,
+            Position(position),
+            Rotation(rotation),
+            shape,
+            Collider(collider),
+         ),
+      );
+      Ok(())
    }
 }
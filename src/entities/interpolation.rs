@@ -1,7 +1,7 @@
 //! Components for smoothing out choppy movement.
 
 use hecs::World;
-use tetra::math::Vec2::{self};
+use vek::Vec2;
 
 use crate::interpolation::Interpolated;
 
@@ -1,22 +1,41 @@
 //! Entities and components.
 
+#[cfg(feature = "game")]
 use hecs::World;
-use tetra::math::Vec2;
+#[cfg(feature = "game")]
 use tetra::{graphics, Context};
+use vek::Vec2;
 
+#[cfg(feature = "game")]
+use crate::common::Rect;
+#[cfg(feature = "game")]
 use crate::input::Input;
+#[cfg(feature = "game")]
 use crate::physics::Physics;
+#[cfg(feature = "game")]
 use crate::resources::Resources;
+#[cfg(feature = "game")]
 use crate::transform::TransformStack;
 
+#[cfg(feature = "game")]
 use self::camera::Camera;
+#[cfg(feature = "game")]
 use self::checkpoint::Checkpoint;
+#[cfg(feature = "game")]
 use self::dead::Kill;
+#[cfg(feature = "game")]
 use self::interpolation::tick_interpolation;
+#[cfg(feature = "game")]
+use self::out_of_bounds::OutOfBounds;
+#[cfg(feature = "game")]
 use self::physics::tick_physics;
+#[cfg(feature = "game")]
 use self::player::Player;
+#[cfg(feature = "game")]
 use self::text::Text;
+#[cfg(feature = "game")]
 use self::trigger::Trigger;
+#[cfg(feature = "game")]
 use self::zones::Zones;
 
 pub mod camera;
@@ -24,8 +43,10 @@ pub mod checkpoint;
 pub mod colliders;
 pub mod dead;
 pub mod interpolation;
+pub mod out_of_bounds;
 pub mod physics;
 pub mod player;
+pub mod shapes;
 pub mod text;
 pub mod trigger;
 pub mod zones;
@@ -44,13 +65,19 @@ impl Default for Position {
 /// The rotation component, expressed in radians.
 pub struct Rotation(pub f32);
 
+/// The name component, holding the name an entity's object was given in the map. Entities can be
+/// looked up by their names through the [`ObjectRegistry`][crate::map::ObjectRegistry] resource.
+pub struct Name(pub String);
+
 /// The size component.
 pub struct Size(pub Vec2<f32>);
 
 /// Ticks all the systems.
+#[cfg(feature = "game")]
 pub fn tick_systems(ctx: &mut Context, world: &mut World, physics: &mut Physics, input: &Input) {
    Player::tick_controls(ctx, world, physics, input);
    Player::tick(world, physics);
+   OutOfBounds::tick(world, physics);
    Trigger::tick(world, physics);
    Checkpoint::tick(world);
    Kill::tick(world);
@@ -59,19 +86,23 @@ pub fn tick_systems(ctx: &mut Context, world: &mut World, physics: &mut Physics,
    Camera::tick(world, physics);
 }
 
-/// Draws with all the systems.
+/// Draws with all the systems. Zones and text outside of `viewport` (the world-space rectangle
+/// visible on the screen) are skipped.
+#[cfg(feature = "game")]
 pub fn draw_systems(
    ctx: &mut Context,
    tstack: &mut TransformStack,
    resources: &mut Resources,
    world: &mut World,
    physics: &mut Physics,
+   viewport: Rect,
+   tile_size: Vec2<f32>,
 ) -> anyhow::Result<()> {
    graphics::set_color_mask(ctx, false, false, true, true);
-   Zones::draw(ctx, resources, world);
+   Zones::draw(ctx, resources, world, viewport)?;
 
    graphics::set_color_mask(ctx, true, true, false, true);
-   Text::draw(ctx, tstack, resources, world)?;
+   Text::draw(ctx, tstack, resources, world, viewport, tile_size)?;
    Player::draw(ctx, world, physics)?;
 
    graphics::set_color_mask(ctx, true, true, true, true);
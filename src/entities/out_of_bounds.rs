@@ -0,0 +1,146 @@
+//! Out-of-bounds regions, and the playable area, which stop players from falling forever.
+
+use std::str::FromStr;
+
+use hecs::{Entity, World};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use vek::Vec2;
+
+use crate::common::{rect, vector, Rect};
+use crate::physics::Physics;
+
+use super::checkpoint::RespawnPosition;
+use super::dead::{Alive, Kill};
+use super::player::Player;
+use super::{Position, Size};
+
+/// What happens to players that leave the playable area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutOfBoundsAction {
+   /// The player dies, and respawns at its respawn position.
+   Kill,
+   /// The player is teleported back to its respawn position, without dying.
+   Teleport,
+}
+
+impl FromStr for OutOfBoundsAction {
+   type Err = serde::de::value::Error;
+
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      Self::deserialize(s.into_deserializer())
+   }
+}
+
+/// Component marking that an entity is an out-of-bounds region, such as the bottom of a pit.
+/// Players that enter the region are killed or teleported, depending on the region's action.
+pub struct OutOfBounds {
+   /// What happens to players that enter the region.
+   pub action: OutOfBoundsAction,
+}
+
+/// Component marking that an entity is the map-wide playable area. Players that leave it are
+/// killed, so that they don't fall forever after clipping out of a room.
+pub struct PlayableArea;
+
+impl OutOfBounds {
+   /// Spawns an out-of-bounds region into the world.
+   pub fn spawn(world: &mut World, entity: Entity, rect: Rect, action: OutOfBoundsAction) {
+      world.spawn_at(
+         entity,
+         (
+            OutOfBounds { action },
+            Position(rect.top_left()),
+            Size(vector(rect.width, rect.height)),
+         ),
+      );
+   }
+
+   /// Spawns the map-wide playable area into the world.
+   pub fn spawn_playable_area(world: &mut World, entity: Entity, rect: Rect) {
+      world.spawn_at(
+         entity,
+         (
+            PlayableArea,
+            Position(rect.top_left()),
+            Size(vector(rect.width, rect.height)),
+         ),
+      );
+   }
+
+   /// Returns the rectangles of all out-of-bounds regions in the world, along with their actions.
+   fn regions(world: &World) -> Vec<(Rect, OutOfBoundsAction)> {
+      world
+         .query::<(&OutOfBounds, &Position, &Size)>()
+         .iter()
+         .map(|(_id, (bounds, &Position(position), &Size(size)))| {
+            (rect(position, size), bounds.action)
+         })
+         .collect()
+   }
+
+   /// Returns the rectangles of the playable areas in the world.
+   fn playable_areas(world: &World) -> Vec<Rect> {
+      world
+         .query::<(&PlayableArea, &Position, &Size)>()
+         .iter()
+         .map(|(_id, (_, &Position(position), &Size(size)))| rect(position, size))
+         .collect()
+   }
+
+   /// Returns what happens to a player at the given position, or `None` if the position is in
+   /// bounds. Positions are in bounds if they're inside the playable area (or the world doesn't
+   /// have one), and outside of all out-of-bounds regions.
+   fn action_at(
+      regions: &[(Rect, OutOfBoundsAction)],
+      playable_areas: &[Rect],
+      position: Vec2<f32>,
+   ) -> Option<OutOfBoundsAction> {
+      if let Some(&(_, action)) = regions.iter().find(|(region, _)| region.contains_point(position))
+      {
+         return Some(action);
+      }
+      let in_playable_area = playable_areas.is_empty()
+         || playable_areas.iter().any(|area| area.contains_point(position));
+      (!in_playable_area).then_some(OutOfBoundsAction::Kill)
+   }
+
+   /// Returns whether the given position is in bounds, see [`OutOfBounds::action_at`].
+   pub fn is_in_bounds(world: &World, position: Vec2<f32>) -> bool {
+      Self::action_at(
+         &Self::regions(world),
+         &Self::playable_areas(world),
+         position,
+      )
+      .is_none()
+   }
+
+   /// Ticks out-of-bounds regions, killing or teleporting players that are out of bounds.
+   pub fn tick(world: &mut World, physics: &mut Physics) {
+      let regions = Self::regions(world);
+      let playable_areas = Self::playable_areas(world);
+      if regions.is_empty() && playable_areas.is_empty() {
+         return;
+      }
+
+      let mut out_of_bounds = Vec::new();
+      for (id, (_, &Position(position), &RespawnPosition(respawn_position))) in
+         world.query_mut::<Alive<(&Player, &Position, &RespawnPosition)>>()
+      {
+         if let Some(action) = Self::action_at(&regions, &playable_areas, position) {
+            out_of_bounds.push((id, action, respawn_position));
+         }
+      }
+      for (player, action, respawn_position) in out_of_bounds {
+         match action {
+            OutOfBoundsAction::Kill => {
+               let _ = world.insert_one(player, Kill::after(1));
+            }
+            OutOfBoundsAction::Teleport => {
+               Player::teleport(world, physics, player, respawn_position);
+            }
+         }
+      }
+   }
+}
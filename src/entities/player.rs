@@ -1,20 +1,32 @@
 //! Components and systems for the player entity.
 
+use std::str::FromStr;
 use std::time::Duration;
 
-use hecs::{Component, Entity, World};
+#[cfg(feature = "game")]
+use hecs::Component;
+use hecs::{Entity, World};
 use rapier2d::math::Isometry;
 use rapier2d::prelude::{
-   Ball, CoefficientCombineRule, ColliderBuilder, Cuboid, InteractionGroups, RigidBodyBuilder,
-   RigidBodyHandle, SharedShape,
+   Ball, CoefficientCombineRule, ColliderBuilder, ColliderHandle, Cuboid, InteractionGroups,
+   RigidBodyBuilder, RigidBodyHandle, SharedShape,
 };
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+#[cfg(feature = "game")]
 use tetra::graphics::mesh::{GeometryBuilder, ShapeStyle};
+#[cfg(feature = "game")]
 use tetra::graphics::DrawParams;
-use tetra::math::Vec2;
+#[cfg(feature = "game")]
 use tetra::Context;
+use vek::Vec2;
 
+#[cfg(feature = "game")]
 use crate::assets::RemappableColors;
-use crate::common::{rect, stretch_squish, vector, ToNalgebraVector2, ToVekVec2};
+#[cfg(feature = "game")]
+use crate::common::{rect, stretch_squish};
+use crate::common::{vector, ToNalgebraVector2, ToVekVec2};
+#[cfg(feature = "game")]
 use crate::input::{Button, Input};
 use crate::physics::{CollisionGroups, Physics};
 use crate::tween::{easings, Tween};
@@ -27,7 +39,8 @@ use super::physics::{Collider, RigidBody};
 use super::{Position, Size};
 
 /// A player's morph state.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[repr(u8)]
 pub enum Morph {
    /// No morph kind detected yet.
@@ -46,6 +59,23 @@ pub enum Morph {
 
 impl Morph {
    pub const FROM_U8: &'static [Morph] = &[Morph::None, Morph::Unshaped, Morph::Platformer];
+
+   /// Returns the collision group of barriers that are solid to this morph.
+   pub fn barrier_group(self) -> u32 {
+      match self {
+         Morph::None => 0,
+         Morph::Unshaped => CollisionGroups::UNSHAPED_BARRIERS,
+         Morph::Platformer => CollisionGroups::PLATFORMER_BARRIERS,
+      }
+   }
+}
+
+impl FromStr for Morph {
+   type Err = serde::de::value::Error;
+
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      Self::deserialize(s.into_deserializer())
+   }
 }
 
 /// Parameters for body physics.
@@ -80,6 +110,7 @@ impl Unshaped {
       }
    }
 
+   #[cfg(feature = "game")]
    fn tick_controls(world: &mut World, physics: &mut Physics, input: &Input) {
       const ACCELERATION: f32 = 50.0;
       const DAMPING: f32 = 0.97;
@@ -140,6 +171,7 @@ impl Platformer {
    }
 
    /// Ticks the player controls.
+   #[cfg(feature = "game")]
    fn tick_controls(ctx: &mut Context, world: &mut World, physics: &mut Physics, input: &Input) {
       const ACCELERATION: f32 = 175.0;
       const DECELERATION: f32 = 0.8;
@@ -155,8 +187,8 @@ impl Platformer {
       // retains control of their walking direction.
       const WALKING_VELOCITY: f32 = 12.0;
 
-      for (_id, (_, platformer, &RigidBody(body_handle))) in
-         world.query_mut::<Alive<(&Player, &mut Platformer, &RigidBody)>>()
+      for (_id, (_, platformer, &RigidBody(body_handle), &Collider(collider_handle))) in
+         world.query_mut::<Alive<(&Player, &mut Platformer, &RigidBody, &Collider)>>()
       {
          let is_walking = {
             let body = &mut physics.rigid_bodies[body_handle];
@@ -206,6 +238,7 @@ impl Platformer {
             platformer.remaining_jump_ticks = 0;
          }
 
+         let is_jumping = platformer.remaining_jump_ticks > 0;
          platformer.air_time = platformer.air_time.saturating_sub(1);
          platformer.jump_buffer = platformer.jump_buffer.saturating_sub(1);
          platformer.remaining_jump_ticks = platformer.remaining_jump_ticks.saturating_sub(1);
@@ -216,6 +249,19 @@ impl Platformer {
          } else {
             1.0
          };
+         // Stick to slopes, such that the player doesn't slide down them when standing still, and
+         // doesn't bounce off of them when walking down. This is done by disabling gravity and
+         // only letting the player move along the slope's surface.
+         let slope_normal = Self::ground_normal(physics, collider_handle)
+            .filter(|normal| normal.x.abs() > 0.01 && !is_jumping);
+         let body = &mut physics.rigid_bodies[body_handle];
+         if let Some(normal) = slope_normal {
+            let tangent = vector(-normal.y, normal.x);
+            velocity = tangent * velocity.dot(tangent);
+            body.set_gravity_scale(0.0, true);
+         } else {
+            body.set_gravity_scale(Self::physics_params().gravity_scale, true);
+         }
          body.set_linvel(velocity.nalgebra(), true);
 
          let velocity = body.linvel();
@@ -244,11 +290,42 @@ impl Platformer {
             &physics.colliders,
             &Isometry::new(translation.nalgebra(), 0.0),
             &cuboid,
-            InteractionGroups::new(CollisionGroups::PLAYER, CollisionGroups::SOLIDS),
+            InteractionGroups::new(
+               CollisionGroups::PLAYER,
+               CollisionGroups::SOLIDS | Morph::Platformer.barrier_group(),
+            ),
             None,
          )
          .is_some()
    }
+
+   /// Returns the normal of the ground the (player's) collider is standing on, pointing away from
+   /// the ground. If the collider stands on multiple surfaces, the one facing upwards the most is
+   /// picked. Surfaces that are too steep to stand on are not considered ground.
+   fn ground_normal(physics: &Physics, collider: ColliderHandle) -> Option<Vec2<f32>> {
+      // The minimum upwards component of a normal for its surface to be considered ground.
+      const MIN_UPWARDS: f32 = 0.3;
+
+      physics
+         .narrow_phase
+         .contacts_with(collider)
+         .filter(|pair| pair.has_any_active_contact)
+         .flat_map(|pair| {
+            pair.manifolds.iter().filter(|manifold| manifold.data.num_active_contacts() > 0).map(
+               move |manifold| {
+                  // Manifold normals point from the first collider to the second one.
+                  let normal = manifold.data.normal.vek();
+                  if pair.collider1 == collider {
+                     -normal
+                  } else {
+                     normal
+                  }
+               },
+            )
+         })
+         .filter(|normal| -normal.y >= MIN_UPWARDS)
+         .min_by(|a, b| a.y.total_cmp(&b.y))
+   }
 }
 
 /// Marker component and namespace for player-related functions.
@@ -273,6 +350,7 @@ impl Player {
    }
 
    /// Ticks the players' controls.
+   #[cfg(feature = "game")]
    pub fn tick_controls(
       ctx: &mut Context,
       world: &mut World,
@@ -283,6 +361,23 @@ impl Player {
       Platformer::tick_controls(ctx, world, physics, input);
    }
 
+   /// Moves a player to the given position instantly, stopping it in place.
+   pub fn teleport(world: &mut World, physics: &mut Physics, entity: Entity, position: Vec2<f32>) {
+      let mut query = world
+         .query_one::<(&mut InterpolatedPosition, &RigidBody)>(entity)
+         .expect("teleported entity must exist");
+      if let Some((InterpolatedPosition(ip), &RigidBody(body_handle))) = query.get() {
+         let body = &mut physics.rigid_bodies[body_handle];
+         // Prevent interpolation jank by resetting the current and previous position to the
+         // same value.
+         ip.set(position);
+         ip.reset();
+         body.set_translation(position.nalgebra(), true);
+         // TODO(liquidev): Velocity-preserving death might be a neat mechanic.
+         body.set_linvel(vector(0.0, 0.0).nalgebra(), true);
+      }
+   }
+
    /// Ticks players.
    pub fn tick(world: &mut World, physics: &mut Physics) {
       // Kill the player if they touch a deadly collision group.
@@ -306,34 +401,13 @@ impl Player {
       }
 
       // Respawn all dead players.
-      let mut respawn = Vec::new();
-      for (
-         id,
-         (
-            _player,
-            &RespawnPosition(respawn_position),
-            InterpolatedPosition(ip),
-            &RigidBody(body_handle),
-            &Dead,
-         ),
-      ) in world.query_mut::<(
-         &Player,
-         &RespawnPosition,
-         &mut InterpolatedPosition,
-         &RigidBody,
-         &Dead,
-      )>() {
-         let body = &mut physics.rigid_bodies[body_handle];
-         // Prevent interpolation jank by resetting the current and previous position to the
-         // same value.
-         ip.set(respawn_position);
-         ip.reset();
-         body.set_translation(respawn_position.nalgebra(), true);
-         // TODO(liquidev): Velocity-preserving death might be a neat mechanic.
-         body.set_linvel(vector(0.0, 0.0).nalgebra(), true);
-         respawn.push(id);
-      }
-      for player in respawn {
+      let respawn: Vec<_> = world
+         .query_mut::<(&Player, &RespawnPosition, &Dead)>()
+         .into_iter()
+         .map(|(id, (_player, &RespawnPosition(respawn_position), &Dead))| (id, respawn_position))
+         .collect();
+      for (player, respawn_position) in respawn {
+         Self::teleport(world, physics, player, respawn_position);
          let _ = world.remove_one::<Dead>(player);
          world.get_mut::<Player>(player).unwrap().start_spawn_animation();
       }
@@ -390,11 +464,21 @@ impl Player {
       };
       collider.set_shape(params.shape);
       collider.set_restitution(params.restitution);
+      collider.set_collision_groups(Self::collision_groups(morph));
       body.set_gravity_scale(params.gravity_scale, true);
       world.get_mut::<Size>(player).unwrap().0 = params.size;
    }
 
+   /// Returns the collision groups of a player with the given morph.
+   fn collision_groups(morph: Morph) -> InteractionGroups {
+      InteractionGroups::new(
+         CollisionGroups::PLAYER,
+         CollisionGroups::SOLIDS | CollisionGroups::TRIGGERS | morph.barrier_group(),
+      )
+   }
+
    /// Draws a morph with the state `M`.
+   #[cfg(feature = "game")]
    fn draw_morph<M, F>(ctx: &mut Context, world: &mut World, mut draw: F) -> anyhow::Result<()>
    where
       M: Component,
@@ -412,6 +496,7 @@ impl Player {
    }
 
    /// Draws players.
+   #[cfg(feature = "game")]
    pub fn draw(ctx: &mut Context, world: &mut World, physics: &mut Physics) -> anyhow::Result<()> {
       Self::draw_morph::<Unshaped, _>(
          ctx,
@@ -465,9 +550,9 @@ impl Player {
             );
             GeometryBuilder::new()
                .set_color(RemappableColors::BACKGROUND)
-               .rectangle(ShapeStyle::Fill, rect)?
+               .rectangle(ShapeStyle::Fill, rect.into())?
                .set_color(RemappableColors::FOREGROUND)
-               .rectangle(ShapeStyle::Stroke(0.1), rect)?
+               .rectangle(ShapeStyle::Stroke(0.1), rect.into())?
                .build_mesh(ctx)?
                .draw(ctx, DrawParams::new());
             Ok(())
@@ -487,10 +572,7 @@ impl Player {
       let collider = ColliderBuilder::cuboid(size.x / 2.0, size.y / 2.0)
          .friction(0.0)
          .friction_combine_rule(CoefficientCombineRule::Min)
-         .collision_groups(InteractionGroups::new(
-            CollisionGroups::PLAYER,
-            CollisionGroups::SOLIDS | CollisionGroups::TRIGGERS,
-         ))
+         .collision_groups(Self::collision_groups(Morph::None))
          .user_data(u64::from(entity.to_bits()) as u128)
          .build();
       let collider =
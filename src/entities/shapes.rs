@@ -0,0 +1,132 @@
+//! Shapes of static entities, such as colliders, zones, and triggers.
+
+use rapier2d::prelude::{ColliderBuilder, Point};
+#[cfg(feature = "game")]
+use tetra::graphics::Color;
+#[cfg(feature = "game")]
+use vek::Mat2;
+use vek::Vec2;
+
+use crate::common::{cross, vector};
+#[cfg(feature = "game")]
+use crate::meshes::MeshBuilder;
+
+/// The shape component. Shapes are relative to the entity's position; rectangles and ellipses are
+/// centered on it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+   /// A rectangle with the given size.
+   Rectangle(Vec2<f32>),
+   /// An ellipse with the given size.
+   Ellipse(Vec2<f32>),
+   /// A closed polygon, which may be concave.
+   Polygon(Vec<Vec2<f32>>),
+   /// An open polyline. Polylines do not enclose an area.
+   Polyline(Vec<Vec2<f32>>),
+}
+
+impl Shape {
+   /// The number of points ellipses are approximated with.
+   const ELLIPSE_POINTS: usize = 32;
+
+   /// Returns whether the shape encloses an area.
+   pub fn has_area(&self) -> bool {
+      !matches!(self, Self::Polyline(_))
+   }
+
+   /// Returns the radius of the smallest circle centered on the entity's position that contains
+   /// the whole shape, no matter how it's rotated.
+   pub fn bounding_radius(&self) -> f32 {
+      match self {
+         Self::Rectangle(size) | Self::Ellipse(size) => size.magnitude() / 2.0,
+         Self::Polygon(points) | Self::Polyline(points) => {
+            points.iter().map(|point| point.magnitude()).fold(0.0, f32::max)
+         }
+      }
+   }
+
+   /// Creates a collider builder for the shape. Ellipses that aren't circles are approximated with
+   /// polygons, and concave polygons are decomposed into convex parts.
+   pub fn collider(&self) -> anyhow::Result<ColliderBuilder> {
+      Ok(match self {
+         Self::Rectangle(size) => ColliderBuilder::cuboid(size.x / 2.0, size.y / 2.0),
+         Self::Ellipse(size) if size.x == size.y => ColliderBuilder::ball(size.x / 2.0),
+         Self::Ellipse(size) => ColliderBuilder::convex_hull(&to_points(&ellipse(*size)))
+            .ok_or_else(|| anyhow::anyhow!("ellipse is degenerate"))?,
+         Self::Polygon(points) if is_convex(points) => {
+            ColliderBuilder::convex_hull(&to_points(points))
+               .ok_or_else(|| anyhow::anyhow!("polygon is degenerate"))?
+         }
+         Self::Polygon(points) => {
+            let n = points.len() as u32;
+            let segments: Vec<_> = (0..n).map(|i| [i, (i + 1) % n]).collect();
+            ColliderBuilder::convex_decomposition(&to_points(points), &segments)
+         }
+         Self::Polyline(points) => {
+            if points.len() < 2 {
+               anyhow::bail!("polyline must have at least two points");
+            }
+            ColliderBuilder::polyline(to_points(points), None)
+         }
+      })
+   }
+
+   /// Adds the shape into a mesh, placed at the given position and rotated by the given angle.
+   /// Polylines do not enclose an area, so nothing is added for them.
+   #[cfg(feature = "game")]
+   pub fn build_mesh(
+      &self,
+      builder: &mut MeshBuilder,
+      position: Vec2<f32>,
+      rotation: f32,
+      color: Color,
+   ) {
+      let rotation = Mat2::rotation_z(rotation);
+      let place = |points: &[Vec2<f32>]| -> Vec<_> {
+         points.iter().map(|&point| position + rotation * point).collect()
+      };
+      match self {
+         Self::Rectangle(size) => {
+            let half = *size / 2.0;
+            let corners = [
+               -half,
+               vector(half.x, -half.y),
+               half,
+               vector(-half.x, half.y),
+            ];
+            builder.convex_polygon(&place(&corners), color)
+         }
+         Self::Ellipse(size) => builder.convex_polygon(&place(&ellipse(*size)), color),
+         Self::Polygon(points) => builder.polygon(&place(points), color),
+         Self::Polyline(_) => builder,
+      };
+   }
+}
+
+/// Returns the points of an ellipse with the given size, centered on the origin.
+fn ellipse(size: Vec2<f32>) -> Vec<Vec2<f32>> {
+   (0..Shape::ELLIPSE_POINTS)
+      .map(|i| {
+         let angle = i as f32 / Shape::ELLIPSE_POINTS as f32 * std::f32::consts::TAU;
+         vector(angle.cos(), angle.sin()) * size / 2.0
+      })
+      .collect()
+}
+
+/// Checks whether a polygon is convex, regardless of its winding order.
+fn is_convex(points: &[Vec2<f32>]) -> bool {
+   let n = points.len();
+   let turns = (0..n).map(|i| {
+      let (a, b, c) = (points[i], points[(i + 1) % n], points[(i + 2) % n]);
+      cross(b - a, c - b)
+   });
+   let (left, right) = turns.fold((false, false), |(left, right), turn| {
+      (left || turn > 0.0, right || turn < 0.0)
+   });
+   !(left && right)
+}
+
+/// Converts vectors to rapier points.
+fn to_points(points: &[Vec2<f32>]) -> Vec<Point<f32>> {
+   points.iter().map(|point| Point::new(point.x, point.y)).collect()
+}
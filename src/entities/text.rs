@@ -1,14 +1,23 @@
 //! Text entity.
 
 use hecs::{Entity, World};
+#[cfg(feature = "game")]
 use tetra::graphics::DrawParams;
+#[cfg(feature = "game")]
 use tetra::Context;
+#[cfg(feature = "game")]
+use vek::Vec2;
 
-use crate::assets::{FontFamily, Fonts, RemappableColors};
+use crate::assets::FontFamily;
+#[cfg(feature = "game")]
+use crate::assets::{Fonts, RemappableColors};
+#[cfg(feature = "game")]
+use crate::common::rect;
 use crate::common::{Rect, RectVectors};
-use crate::map::Map;
+#[cfg(feature = "game")]
 use crate::resources::Resources;
 use crate::tiled::TextHAlign;
+#[cfg(feature = "game")]
 use crate::transform::{self, TransformStack};
 
 use super::{Position, Size};
@@ -22,29 +31,34 @@ pub struct Text {
 }
 
 impl Text {
-   /// Draws text entities.
+   /// Draws text entities. Text is laid out in the map's pixels, so `tile_size` is needed to
+   /// convert it to world units.
+   #[cfg(feature = "game")]
    pub fn draw(
       ctx: &mut Context,
       tstack: &mut TransformStack,
       resources: &mut Resources,
       world: &mut World,
+      viewport: Rect,
+      tile_size: Vec2<f32>,
    ) -> anyhow::Result<()> {
       tstack.save(ctx);
-      transform::scale(ctx, Map::tile_size().recip());
+      transform::scale(ctx, tile_size.recip());
 
       let fonts = resources.get_mut::<Fonts>().unwrap();
       for (_id, (&Position(position), &Size(size), text)) in
          world.query_mut::<(&Position, &Size, &Text)>()
       {
+         if !viewport.intersects(&rect(position, size)) {
+            continue;
+         }
          let font_family = &mut fonts[text.font_family];
          font_family.load(ctx, text.size)?.draw_aligned(
             ctx,
             &text.text,
             text.h_align,
-            size.x * Map::tile_size().x,
-            DrawParams::new()
-               .position(position * Map::tile_size())
-               .color(RemappableColors::FOREGROUND),
+            size.x * tile_size.x,
+            DrawParams::new().position(position * tile_size).color(RemappableColors::FOREGROUND),
          );
       }
 
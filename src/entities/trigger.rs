@@ -1,13 +1,15 @@
 //! Trigger entities.
 
 use hecs::{CommandBuffer, Entity, World};
-use rapier2d::prelude::{ColliderBuilder, InteractionGroups};
+use rapier2d::prelude::InteractionGroups;
+use vek::Vec2;
 
-use crate::common::{Rect, RectVectors, ToNalgebraVector2};
+use crate::common::ToNalgebraVector2;
 use crate::physics::{CollisionGroups, Physics};
 
 use super::physics::Collider;
-use super::{Position, Size};
+use super::shapes::Shape;
+use super::{Position, Rotation};
 
 /// Component added to trigger targets when triggers linking to them are activated.
 pub struct Triggered {
@@ -72,17 +74,23 @@ impl Trigger {
       triggers.run_on(world);
    }
 
-   /// Spawns a trigger entity into the world.
+   /// Spawns a trigger entity into the world. The trigger's shape must enclose an area.
    pub fn spawn(
       world: &mut World,
       physics: &mut Physics,
       entity: Entity,
-      rect: Rect,
+      position: Vec2<f32>,
+      rotation: f32,
+      shape: Shape,
       trigger: Trigger,
-   ) {
-      let half_extents = rect.size() / 2.0;
-      let collider = ColliderBuilder::cuboid(half_extents.x, half_extents.y)
-         .translation(rect.center().nalgebra())
+   ) -> anyhow::Result<()> {
+      if !shape.has_area() {
+         anyhow::bail!("trigger shape must enclose an area");
+      }
+      let collider = shape
+         .collider()?
+         .translation(position.nalgebra())
+         .rotation(rotation)
          .collision_groups(InteractionGroups::new(
             CollisionGroups::TRIGGERS,
             CollisionGroups::PLAYER,
@@ -95,10 +103,12 @@ impl Trigger {
          entity,
          (
             trigger,
-            Position(rect.position()),
-            Size(rect.size()),
+            Position(position),
+            Rotation(rotation),
+            shape,
             Collider(collider_handle),
          ),
-      )
+      );
+      Ok(())
    }
 }
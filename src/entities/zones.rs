@@ -1,20 +1,33 @@
 //! Zones, the core mechanic of the game.
 
+#[cfg(feature = "game")]
+use std::collections::HashMap;
+
 use hecs::{Component, Entity, World};
+#[cfg(feature = "game")]
 use nanorand::Rng;
-use rapier2d::prelude::{ColliderBuilder, InteractionGroups};
+use rapier2d::prelude::InteractionGroups;
+#[cfg(feature = "game")]
+use tetra::graphics::mesh::Mesh;
+#[cfg(feature = "game")]
 use tetra::graphics::{Color, DrawParams};
+#[cfg(feature = "game")]
 use tetra::Context;
 use vek::Vec2;
 
-use crate::assets::WhiteTexture;
-use crate::common::{vector, ToNalgebraVector2};
+use crate::common::ToNalgebraVector2;
+#[cfg(feature = "game")]
+use crate::common::{rect, vector, Rect};
+#[cfg(feature = "game")]
+use crate::meshes::MeshBuilder;
 use crate::physics::{CollisionGroups, Physics};
+#[cfg(feature = "game")]
 use crate::resources::Resources;
 
 use super::physics::Collider;
 use super::player::Morph;
-use super::{Position, Rotation, Size};
+use super::shapes::Shape;
+use super::{Position, Rotation};
 
 /// Auto-generated helper trait for providing zones with data like palette indices.
 pub trait ZoneData: Component {
@@ -36,7 +49,9 @@ macro_rules! zone_index {
 #[allow(unused_variables)]
 pub trait ZoneSpawn: Component {
    /// Injects extra behavior for spawning the entity into the world.
-   fn spawn(world: &mut World, physics: &mut Physics, entity: Entity) {}
+   fn spawn(world: &mut World, physics: &mut Physics, entity: Entity) -> anyhow::Result<()> {
+      Ok(())
+   }
 }
 
 fn init_shaped_zone_collider(
@@ -45,11 +60,13 @@ fn init_shaped_zone_collider(
    entity: Entity,
    group_memberships: u32,
    user_data: u128,
-) {
+) -> anyhow::Result<()> {
    let Position(position) = *world.get(entity).unwrap();
-   let Size(size) = *world.get(entity).unwrap();
    let Rotation(rotation) = *world.get(entity).unwrap();
-   let collider = ColliderBuilder::cuboid(size.x / 2.0, size.y / 2.0)
+   let collider = world
+      .get::<Shape>(entity)
+      .unwrap()
+      .collider()?
       .translation(position.nalgebra())
       .rotation(rotation)
       .collision_groups(InteractionGroups::new(
@@ -60,6 +77,7 @@ fn init_shaped_zone_collider(
       .build();
    let collider = physics.colliders.insert(collider);
    world.insert_one(entity, Collider(collider)).unwrap();
+   Ok(())
 }
 
 /// Marker component for platformer zones.
@@ -67,7 +85,7 @@ pub struct PlatformerZone;
 zone_index!(PlatformerZone, 1, Some(Morph::Platformer));
 
 impl ZoneSpawn for PlatformerZone {
-   fn spawn(world: &mut World, physics: &mut Physics, entity: Entity) {
+   fn spawn(world: &mut World, physics: &mut Physics, entity: Entity) -> anyhow::Result<()> {
       init_shaped_zone_collider(
          world,
          physics,
@@ -83,23 +101,45 @@ pub struct DeadlyZone;
 zone_index!(DeadlyZone, 2, None);
 
 impl ZoneSpawn for DeadlyZone {
-   fn spawn(world: &mut World, physics: &mut Physics, entity: Entity) {
-      init_shaped_zone_collider(world, physics, entity, CollisionGroups::DEADLY, 0);
+   fn spawn(world: &mut World, physics: &mut Physics, entity: Entity) -> anyhow::Result<()> {
+      init_shaped_zone_collider(world, physics, entity, CollisionGroups::DEADLY, 0)
    }
 }
 
 /// Zone rendering parameters.
+#[cfg(feature = "game")]
 struct RenderParams {
    offset: Vec2<f32>,
 }
 
 /// The default render parameter function.
+#[cfg(feature = "game")]
 fn default_render_params() -> RenderParams {
    RenderParams {
       offset: vector(0.0, 0.0),
    }
 }
 
+/// The cached mesh of a zone. The mesh is relative to the zone's position and rotation, which
+/// are applied when it's drawn.
+#[cfg(feature = "game")]
+struct ZoneMesh {
+   mesh: Mesh,
+   /// The shape and color the mesh was built from. The mesh is rebuilt if they change.
+   shape: Shape,
+   color: Color,
+   /// Whether the zone still exists, as of the last time zones were drawn.
+   alive: bool,
+}
+
+/// The cached meshes of zones, stored in the resources. Meshes hold onto graphics resources and
+/// cannot be components, so they're kept here, keyed by the zones' entities.
+#[cfg(feature = "game")]
+#[derive(Default)]
+pub struct ZoneMeshes {
+   meshes: HashMap<Entity, ZoneMesh>,
+}
+
 /// Namespace struct for zone-related systems.
 pub struct Zones;
 
@@ -107,65 +147,107 @@ impl Zones {
    /// The maximum zone index.
    pub const MAX: usize = 32;
 
-   /// Draws zones to the screen.
-   pub fn draw(ctx: &mut Context, resources: &mut Resources, world: &mut World) {
+   /// Draws zones to the screen. Zones outside of the viewport are skipped.
+   #[cfg(feature = "game")]
+   pub fn draw(
+      ctx: &mut Context,
+      resources: &mut Resources,
+      world: &mut World,
+      viewport: Rect,
+   ) -> anyhow::Result<()> {
+      let meshes = resources.get_or_insert::<ZoneMeshes, ()>(ZoneMeshes::default);
+      for mesh in meshes.meshes.values_mut() {
+         mesh.alive = false;
+      }
+
       let mut rand = nanorand::tls_rng();
-      Self::draw_zone::<PlatformerZone, _>(ctx, resources, world, default_render_params);
-      Self::draw_zone::<DeadlyZone, _>(ctx, resources, world, || RenderParams {
+      Self::draw_zone::<PlatformerZone, _>(ctx, meshes, world, viewport, default_render_params)?;
+      Self::draw_zone::<DeadlyZone, _>(ctx, meshes, world, viewport, || RenderParams {
          offset: (vector(rand.generate(), rand.generate()) * 2.0 - 1.0) * 0.05,
-      });
+      })?;
+
+      // Drop the meshes of zones that were despawned, eg. because the map was reloaded.
+      meshes.meshes.retain(|_, mesh| mesh.alive);
+      Ok(())
    }
 
-   /// Draws a specific type of zone to the screen.
+   /// Draws a specific type of zone to the screen. Each zone's mesh is built the first time the
+   /// zone is drawn, and reused afterwards until the zone's shape changes.
+   #[cfg(feature = "game")]
    fn draw_zone<T, P>(
       ctx: &mut Context,
-      resources: &mut Resources,
+      meshes: &mut ZoneMeshes,
       world: &mut World,
+      viewport: Rect,
       mut params: P,
-   ) where
+   ) -> anyhow::Result<()>
+   where
       T: ZoneData,
       P: FnMut() -> RenderParams,
    {
-      let WhiteTexture(white_texture) = resources.get().unwrap();
-
-      for (_id, (_zone_tag, &Position(position), &Size(size), &Rotation(rotation))) in
-         world.query_mut::<(&T, &Position, &Size, &Rotation)>()
+      let color = Color {
+         r: 0.0,
+         g: 0.0,
+         b: T::index() as f32 / Self::MAX as f32,
+         a: 1.0,
+      };
+      for (id, (_zone_tag, &Position(position), &Rotation(rotation), shape)) in
+         world.query_mut::<(&T, &Position, &Rotation, &Shape)>()
       {
-         let color = Color {
-            r: 0.0,
-            g: 0.0,
-            b: T::index() as f32 / Self::MAX as f32,
-            a: 1.0,
-         };
+         let up_to_date =
+            meshes.meshes.get(&id).is_some_and(|mesh| mesh.shape == *shape && mesh.color == color);
+         if !up_to_date {
+            let mut mesh = MeshBuilder::new();
+            shape.build_mesh(&mut mesh, vector(0.0, 0.0), 0.0, color);
+            let mesh = ZoneMesh {
+               mesh: mesh.build(ctx)?,
+               shape: shape.clone(),
+               color,
+               alive: true,
+            };
+            meshes.meshes.insert(id, mesh);
+         }
+         let mesh = meshes.meshes.get_mut(&id).unwrap();
+         mesh.alive = true;
+
          let params = params();
-         white_texture.draw(
+         // Cull the zone using a square that fits it no matter how it's rotated.
+         let radius = shape.bounding_radius() + params.offset.magnitude();
+         let bounds = rect(
+            position - vector(radius, radius),
+            vector(radius, radius) * 2.0,
+         );
+         if !viewport.intersects(&bounds) {
+            continue;
+         }
+         mesh.mesh.draw(
             ctx,
-            DrawParams::new()
-               .origin(vector(0.5, 0.5))
-               .scale(size)
-               .position(position + params.offset)
-               .rotation(rotation)
-               .color(color),
+            DrawParams::new().position(position + params.offset).rotation(rotation),
          );
       }
+      Ok(())
    }
 
-   /// Spawns a zone into the world.
+   /// Spawns a zone into the world. The zone's shape must enclose an area.
    pub fn spawn<Z>(
       world: &mut World,
       physics: &mut Physics,
       entity: Entity,
       kind: Z,
-      center: Vec2<f32>,
-      size: Vec2<f32>,
+      position: Vec2<f32>,
       rotation: f32,
-   ) where
+      shape: Shape,
+   ) -> anyhow::Result<()>
+   where
       Z: ZoneData + ZoneSpawn,
    {
+      if !shape.has_area() {
+         anyhow::bail!("zone shape must enclose an area");
+      }
       world.spawn_at(
          entity,
-         (kind, Position(center), Size(size), Rotation(rotation)),
+         (kind, Position(position), Rotation(rotation), shape),
       );
-      <Z as ZoneSpawn>::spawn(world, physics, entity);
+      <Z as ZoneSpawn>::spawn(world, physics, entity)
    }
 }
@@ -17,6 +17,8 @@ pub enum Button {
    Left,
    Right,
    Jump,
+   /// Debugging: toggles between drawing the map with per-chunk and per-region meshes.
+   ToggleMeshBatching,
 }
 
 /// Layer for handling button mappings.
@@ -25,6 +27,12 @@ pub struct Input {
    joystick: Vec2<f32>,
 }
 
+impl Default for Input {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
 impl Input {
    pub fn new() -> Self {
       let mut input = Self {
@@ -44,6 +52,8 @@ impl Input {
       input.key_binding(Key::Right, Button::Right);
       input.key_binding(Key::X, Button::Jump);
 
+      input.key_binding(Key::F3, Button::ToggleMeshBatching);
+
       input
    }
 
@@ -2,6 +2,7 @@
 
 use std::ops::{Add, Mul, Sub};
 
+#[cfg(feature = "game")]
 use tetra::{time, Context};
 use vek::Vec2;
 
@@ -65,6 +66,7 @@ where
    }
 
    /// Like `lerp`, but takes the blending coefficient from the current frame's blend factor.
+   #[cfg(feature = "game")]
    pub fn blend(&self, ctx: &Context) -> T
    where
       T: Lerp<f32>,
@@ -0,0 +1,33 @@
+//! mem.pHlus: the game's modules, shared between the game itself and the asset compiler
+//! (`memphlus-assets`). Only the modules the binaries need are public.
+//!
+//! Everything that renders, plays audio or reads input needs the `game` feature, which is enabled
+//! by default. Without it, only the map and asset compiling code is built, which doesn't need SDL2
+//! or ALSA.
+
+// Without the game, the parts of the map and entities that only the game uses are never used.
+#![cfg_attr(not(feature = "game"), allow(dead_code))]
+
+pub mod assets;
+pub mod common;
+mod entities;
+#[cfg(feature = "game")]
+pub mod input;
+mod interpolation;
+pub mod map;
+#[cfg(feature = "game")]
+mod meshes;
+mod physics;
+#[cfg(feature = "game")]
+mod post_process;
+pub mod resources;
+#[cfg(feature = "game")]
+pub mod state;
+#[cfg(feature = "game")]
+pub mod states;
+mod tiled;
+#[cfg(feature = "game")]
+mod transform;
+mod tween;
+#[cfg(feature = "game")]
+mod watcher;
@@ -1,27 +1,16 @@
-mod assets;
-mod common;
-mod entities;
-mod input;
-mod interpolation;
-mod map;
-mod meshes;
-mod physics;
-mod post_process;
-mod resources;
-mod state;
-mod states;
-mod tiled;
-mod transform;
-mod tween;
+use std::path::{Path, PathBuf};
 
 use anyhow::Context as AnyhowContext;
+use log::{error, info, warn};
 use simple_logger::SimpleLogger;
 use tetra::{Context, ContextBuilder, Event};
 
-use assets::{Fonts, WhiteTexture};
-use input::Input;
-use resources::Resources;
-use state::GameState;
+use memphlus::assets::Fonts;
+use memphlus::input::Input;
+use memphlus::map::Map;
+use memphlus::resources::Resources;
+use memphlus::state::GameState;
+use memphlus::states;
 
 struct Game {
    state: Option<Box<dyn GameState>>,
@@ -59,9 +48,37 @@ impl tetra::State<anyhow::Error> for Game {
    }
 }
 
+/// Checks the map for problems instead of starting the game, and exits with a non-zero exit code
+/// if any errors are found. Warnings are listed, but don't fail the check.
+fn check_map(path: &Path) -> anyhow::Result<()> {
+   let diagnostics = Map::check(path)?;
+   for diagnostic in &diagnostics {
+      diagnostic.log();
+   }
+   let errors = diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count();
+   if diagnostics.is_empty() {
+      info!("{:?}: no problems found", path);
+      Ok(())
+   } else if errors == 0 {
+      warn!("{:?}: found {} warning(s)", path, diagnostics.len());
+      Ok(())
+   } else {
+      error!("{:?}: found {} error(s)", path, errors);
+      std::process::exit(1);
+   }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
    SimpleLogger::new().without_timestamps().init()?;
 
+   // `--check-map [path]` checks the map (by default, the one the game would load) and exits.
+   let args: Vec<String> = std::env::args().collect();
+   if let Some(index) = args.iter().position(|arg| arg == "--check-map") {
+      let path = args.get(index + 1).map(PathBuf::from).unwrap_or_else(Map::source_path);
+      check_map(&path)?;
+      return Ok(());
+   }
+
    let mut ctx = ContextBuilder::new("mem.pHlus", 1280, 720)
       .resizable(true)
       .show_mouse(true)
@@ -70,14 +87,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
       .build()
       .context("could not create tetra::Context")?;
 
-   let state = states::game::State::new(&mut ctx)?;
-   let state: Option<Box<dyn GameState>> = Some(Box::new(state));
    let input = Input::new();
    let mut resources = Resources::new();
 
-   WhiteTexture::insert_to(&mut ctx, &mut resources)?;
    Fonts::load_to(&mut resources)?;
 
+   let state = states::game::State::new(&mut ctx, &mut resources)?;
+   let state: Option<Box<dyn GameState>> = Some(Box::new(state));
+
    ctx.run(|_| {
       Ok(Game {
          state,
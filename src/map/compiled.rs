@@ -0,0 +1,817 @@
+//! Compiled maps: a compact binary form of maps, which loads much faster than Tiled maps.
+//!
+//! A compiled map has all of the work that doesn't depend on the game's state already done:
+//! tiles are sorted into chunks, tile kinds are resolved from the tilesets, object templates are
+//! applied, and solid tiles are merged into colliders.
+//!
+//! The file starts with a header:
+//!
+//! - the magic bytes `MPHM`
+//! - the version of the format (u32)
+//! - a CRC-32 checksum of the map's source files at the time the map was compiled (u32)
+//! - a CRC-32 checksum of the payload (u32)
+//!
+//! The payload follows right after. All numbers are little-endian, and lengths are stored as u32.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context as AnyhowContext;
+use flate2::Crc;
+#[cfg(feature = "game")]
+use hecs::World;
+use log::debug;
+#[cfg(feature = "game")]
+use tetra::Context;
+use vek::{Rgba, Vec2};
+
+use crate::common::vector;
+use crate::entities::zones::Zones;
+#[cfg(feature = "game")]
+use crate::physics::Physics;
+#[cfg(feature = "game")]
+use crate::resources::Resources;
+use crate::tiled::{self, EnumValue, ObjectShape, PropertyValue, TextHAlign, TileFlip};
+
+use super::tiles::{SolidRect, TileKind};
+use super::{Chunk, Loader, Map, MapSettings, Tile, TileLayer, TiledSource, Tileset};
+#[cfg(feature = "game")]
+use super::{Layer, LayerKind, MeshBatching, ObjectRegistry};
+
+/// A compiled map.
+pub struct CompiledMap {
+   settings: MapSettings,
+   tileset: Tileset,
+   /// The files the map was compiled from.
+   sources: Vec<PathBuf>,
+   /// The checksum of the contents of the source files, see [`checksum_sources`].
+   source_checksum: u32,
+   layers: Vec<CompiledLayer>,
+}
+
+/// A layer of a compiled map.
+struct CompiledLayer {
+   name: String,
+   visible: bool,
+   opacity: f32,
+   /// The layer's offset, in world units.
+   offset: Vec2<f32>,
+   parallax: Vec2<f32>,
+   properties: tiled::Properties,
+   kind: CompiledLayerKind,
+}
+
+/// The contents of a compiled layer.
+enum CompiledLayerKind {
+   Tile {
+      chunks: HashMap<(i32, i32), Chunk>,
+      solids: Vec<SolidRect>,
+   },
+   Image(tiled::ImageLayer),
+   /// The layer's objects, with the layer's offset already applied to them.
+   Object(Vec<tiled::Object>),
+}
+
+impl CompiledMap {
+   /// The magic bytes compiled maps start with.
+   const MAGIC: &'static [u8; 4] = b"MPHM";
+   /// The version of the format. This must be bumped whenever the format changes, so that maps
+   /// compiled by older versions of the game are rejected instead of misread.
+   pub const VERSION: u32 = 1;
+   /// The length of the header, in bytes.
+   const HEADER_LENGTH: usize = 16;
+   /// The largest number of tiles a compiled map's tilesets can have, including the empty tile
+   /// with ID 0. This keeps a corrupt tile count from allocating huge amounts of memory.
+   const MAX_TILES: usize = 1 << 16;
+
+   /// Compiles the Tiled map at the given path.
+   pub fn compile(path: &Path) -> anyhow::Result<Self> {
+      let source = TiledSource::load(path)?;
+      let source_checksum = checksum_sources(&source.sources)
+         .with_context(|| format!("cannot read the sources of {:?}", path))?;
+      let tileset = source.tileset;
+      if tileset.kinds.len() > Self::MAX_TILES {
+         anyhow::bail!(
+            "the tilesets of {:?} have {} tiles, but compiled maps can have at most {}",
+            path,
+            tileset.kinds.len(),
+            Self::MAX_TILES
+         );
+      }
+      let tile_size = source.settings.tile_size;
+      let layers = source
+         .layers
+         .into_iter()
+         .map(|layer| {
+            let kind = match layer.kind {
+               tiled::LayerKind::Tile(tiles) => {
+                  let layer = TileLayer::without_colliders(Loader::place_tiles(tiles, &tileset));
+                  CompiledLayerKind::Tile {
+                     solids: layer.merge_solids(&tileset),
+                     chunks: layer.chunks,
+                  }
+               }
+               tiled::LayerKind::Image(image) => CompiledLayerKind::Image(image),
+               tiled::LayerKind::Group { .. } => {
+                  unreachable!("group layers are flattened when the map is loaded")
+               }
+               tiled::LayerKind::Object { mut objects } => {
+                  for object in &mut objects {
+                     object.x += layer.offset_x;
+                     object.y += layer.offset_y;
+                  }
+                  CompiledLayerKind::Object(objects)
+               }
+            };
+            CompiledLayer {
+               name: layer.name,
+               visible: layer.visible,
+               opacity: layer.opacity,
+               offset: vector(layer.offset_x, layer.offset_y) / tile_size,
+               parallax: vector(layer.parallax_x, layer.parallax_y),
+               properties: layer.properties,
+               kind,
+            }
+         })
+         .collect();
+      Ok(Self {
+         settings: source.settings,
+         tileset,
+         sources: source.sources,
+         source_checksum,
+         layers,
+      })
+   }
+
+   /// Returns the files the map was compiled from. The first one is the map file itself.
+   pub fn sources(&self) -> &[PathBuf] {
+      &self.sources
+   }
+
+   /// Checks whether the files the map was compiled from changed since it was compiled. Returns
+   /// `None` if they can't be read, such as in builds that don't ship with them.
+   pub fn sources_changed(&self) -> Option<bool> {
+      match checksum_sources(&self.sources) {
+         Ok(checksum) => Some(checksum != self.source_checksum),
+         Err(error) => {
+            debug!("cannot read the sources of a compiled map: {}", error);
+            None
+         }
+      }
+   }
+
+   /// Writes the compiled map to a file.
+   pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+      let mut payload = Writer::default();
+      payload.map(self);
+      let mut bytes = Vec::with_capacity(Self::HEADER_LENGTH + payload.bytes.len());
+      bytes.extend_from_slice(Self::MAGIC);
+      bytes.extend_from_slice(&Self::VERSION.to_le_bytes());
+      bytes.extend_from_slice(&self.source_checksum.to_le_bytes());
+      bytes.extend_from_slice(&checksum(&payload.bytes).to_le_bytes());
+      bytes.extend_from_slice(&payload.bytes);
+      // The map is written next to its final location and moved into place afterwards, so that
+      // the game never sees a half-written map.
+      let temporary = path.with_extension("tmp");
+      std::fs::write(&temporary, bytes).with_context(|| format!("cannot write {:?}", temporary))?;
+      std::fs::rename(&temporary, path).with_context(|| format!("cannot write {:?}", path))
+   }
+
+   /// Reads a compiled map from a file.
+   ///
+   /// Fails if the file is corrupt, was compiled with a different version of the format, or is
+   /// stale: its source files are available and have changed since it was compiled. If the source
+   /// files aren't available, the compiled map is assumed to be up to date.
+   pub fn read(path: &Path) -> anyhow::Result<Self> {
+      let bytes = std::fs::read(path).with_context(|| format!("cannot read {:?}", path))?;
+      if bytes.len() < Self::HEADER_LENGTH || &bytes[..4] != Self::MAGIC {
+         anyhow::bail!("{:?} is not a compiled map", path);
+      }
+      let mut header = Reader::new(&bytes[4..Self::HEADER_LENGTH]);
+      let version = header.u32()?;
+      if version != Self::VERSION {
+         anyhow::bail!(
+            "{:?} was compiled with format version {}, but version {} is expected",
+            path,
+            version,
+            Self::VERSION
+         );
+      }
+      let source_checksum = header.u32()?;
+      let payload_checksum = header.u32()?;
+
+      let payload = &bytes[Self::HEADER_LENGTH..];
+      if checksum(payload) != payload_checksum {
+         anyhow::bail!("{:?} is corrupt: its checksum does not match", path);
+      }
+      let mut reader = Reader::new(payload);
+      let map = reader.map(source_checksum).with_context(|| format!("{:?} is corrupt", path))?;
+      if !reader.bytes.is_empty() {
+         anyhow::bail!("{:?} is corrupt: it has trailing data", path);
+      }
+
+      if map.sources_changed() == Some(true) {
+         anyhow::bail!(
+            "{:?} is stale: its sources changed since it was compiled",
+            path
+         );
+      }
+      Ok(map)
+   }
+}
+
+impl Map {
+   /// Loads a compiled map into the world, the same way [`Map::load_into_world`] loads Tiled maps.
+   ///
+   /// The map is read completely before anything is added to the world or physics, so if this
+   /// fails, the map can still be loaded from its sources instead.
+   #[cfg(feature = "game")]
+   pub fn load_compiled_into_world(
+      ctx: &mut Context,
+      resources: &mut Resources,
+      world: &mut World,
+      physics: &mut Physics,
+      path: &Path,
+   ) -> anyhow::Result<Self> {
+      let compiled = CompiledMap::read(path)?;
+      let tileset = compiled.tileset;
+      let mut loader = Loader {
+         objects: ObjectRegistry::default(),
+         tile_size: compiled.settings.tile_size,
+         diagnostics: Vec::new(),
+      };
+      let layers = compiled
+         .layers
+         .into_iter()
+         .map(|layer| {
+            let kind = match layer.kind {
+               CompiledLayerKind::Tile { chunks, solids } => LayerKind::Tile(
+                  TileLayer::from_chunks(chunks, &tileset, Some(&solids), physics),
+               ),
+               CompiledLayerKind::Image(image) => loader.create_image_layer(ctx, image),
+               CompiledLayerKind::Object(objects) => {
+                  loader.create_object_layer(&layer.name, objects, world, physics)
+               }
+            };
+            Layer {
+               name: layer.name,
+               visible: layer.visible,
+               opacity: layer.opacity,
+               offset: layer.offset,
+               parallax: layer.parallax,
+               properties: layer.properties,
+               kind,
+            }
+         })
+         .collect();
+      let map = Self {
+         layers,
+         settings: compiled.settings,
+         tileset,
+         mesh_batching: MeshBatching::Regions,
+         // The compiled map is watched along with its sources, so that it's reloaded when either
+         // changes. Once the sources change the compiled map becomes stale, and reloading falls
+         // back to the sources.
+         sources: std::iter::once(path.to_owned()).chain(compiled.sources).collect(),
+      };
+      Ok(loader.finish(map, resources, world))
+   }
+}
+
+/// Computes the CRC-32 checksum of some bytes.
+fn checksum(bytes: &[u8]) -> u32 {
+   let mut crc = Crc::new();
+   crc.update(bytes);
+   crc.sum()
+}
+
+/// Computes the CRC-32 checksum of the contents of a map's source files, which is used to tell
+/// whether a compiled map is stale.
+fn checksum_sources(sources: &[PathBuf]) -> std::io::Result<u32> {
+   let mut crc = Crc::new();
+   for source in sources {
+      crc.update(&std::fs::read(source)?);
+   }
+   Ok(crc.sum())
+}
+
+/// Returns the name a tile kind has in tilesets.
+fn tile_kind_name(kind: TileKind) -> String {
+   match serde_json::to_value(kind) {
+      Ok(serde_json::Value::String(name)) => name,
+      _ => unreachable!("tile kinds serialize to strings"),
+   }
+}
+
+/// Encodes values into the payload of a compiled map.
+#[derive(Default)]
+struct Writer {
+   bytes: Vec<u8>,
+}
+
+impl Writer {
+   fn u8(&mut self, value: u8) {
+      self.bytes.push(value);
+   }
+
+   fn u32(&mut self, value: u32) {
+      self.bytes.extend_from_slice(&value.to_le_bytes());
+   }
+
+   fn i32(&mut self, value: i32) {
+      self.bytes.extend_from_slice(&value.to_le_bytes());
+   }
+
+   fn f32(&mut self, value: f32) {
+      self.bytes.extend_from_slice(&value.to_le_bytes());
+   }
+
+   fn bool(&mut self, value: bool) {
+      self.u8(value as u8);
+   }
+
+   fn len(&mut self, len: usize) {
+      self.u32(len as u32);
+   }
+
+   fn str(&mut self, value: &str) {
+      self.len(value.len());
+      self.bytes.extend_from_slice(value.as_bytes());
+   }
+
+   fn option_str(&mut self, value: Option<&str>) {
+      self.bool(value.is_some());
+      if let Some(value) = value {
+         self.str(value);
+      }
+   }
+
+   fn path(&mut self, path: &Path) {
+      // Paths in maps come from UTF-8 files, so the conversion is lossless for them.
+      self.str(&path.to_string_lossy());
+   }
+
+   fn vec2(&mut self, value: Vec2<f32>) {
+      self.f32(value.x);
+      self.f32(value.y);
+   }
+
+   fn map(&mut self, map: &CompiledMap) {
+      self.settings(&map.settings);
+      self.tileset(&map.tileset);
+      self.len(map.sources.len());
+      for source in &map.sources {
+         self.path(source);
+      }
+      self.len(map.layers.len());
+      for layer in &map.layers {
+         self.layer(layer);
+      }
+   }
+
+   fn settings(&mut self, settings: &MapSettings) {
+      self.vec2(settings.gravity);
+      self.len(settings.palette);
+      self.option_str(settings.name.as_deref());
+      self.option_str(settings.music.as_deref());
+      self.vec2(settings.tile_size);
+   }
+
+   /// Writes the tileset. Only the kinds of tiles that aren't empty are stored, by name, so that
+   /// reordering [`TileKind`]'s variants doesn't break compiled maps.
+   fn tileset(&mut self, tileset: &Tileset) {
+      self.len(tileset.kinds.len());
+      let kinds: Vec<_> =
+         tileset.kinds.iter().enumerate().filter(|(_, &kind)| kind != TileKind::Empty).collect();
+      self.len(kinds.len());
+      for (id, &kind) in kinds {
+         self.len(id);
+         self.str(&tile_kind_name(kind));
+      }
+      let mut barrier_groups: Vec<_> = tileset.barrier_groups.iter().collect();
+      barrier_groups.sort_unstable();
+      self.len(barrier_groups.len());
+      for (&id, &group) in barrier_groups {
+         self.u32(id);
+         self.u32(group);
+      }
+   }
+
+   fn layer(&mut self, layer: &CompiledLayer) {
+      self.str(&layer.name);
+      self.bool(layer.visible);
+      self.f32(layer.opacity);
+      self.vec2(layer.offset);
+      self.vec2(layer.parallax);
+      self.properties(&layer.properties);
+      match &layer.kind {
+         CompiledLayerKind::Tile { chunks, solids } => {
+            self.u8(0);
+            // Chunks are sorted, so that compiling the same map always gives the same bytes.
+            let mut chunks: Vec<_> = chunks.iter().collect();
+            chunks.sort_unstable_by_key(|&(&position, _)| position);
+            self.len(chunks.len());
+            for (&(x, y), chunk) in chunks {
+               self.i32(x);
+               self.i32(y);
+               for tile in &chunk.tiles {
+                  self.u32(tile.id);
+                  self.u8(tile.flip.bits());
+               }
+            }
+            self.len(solids.len());
+            for solid in solids {
+               self.i32(solid.left);
+               self.i32(solid.top);
+               self.i32(solid.right);
+               self.i32(solid.bottom);
+               self.u32(solid.group);
+            }
+         }
+         CompiledLayerKind::Image(image) => {
+            self.u8(1);
+            self.path(&image.image);
+            self.bool(image.repeat_x);
+            self.bool(image.repeat_y);
+         }
+         CompiledLayerKind::Object(objects) => {
+            self.u8(2);
+            self.len(objects.len());
+            for object in objects {
+               self.object(object);
+            }
+         }
+      }
+   }
+
+   fn object(&mut self, object: &tiled::Object) {
+      self.u32(object.id);
+      self.str(object.name());
+      self.str(object.kind());
+      self.f32(object.x);
+      self.f32(object.y);
+      let size = object.size();
+      self.f32(size.x);
+      self.f32(size.y);
+      self.f32(object.rotation());
+      self.properties(&object.properties);
+      match object.shape.as_ref().unwrap_or(&ObjectShape::Rectangle) {
+         ObjectShape::Rectangle => self.u8(0),
+         ObjectShape::Ellipse => self.u8(1),
+         ObjectShape::Point => self.u8(2),
+         ObjectShape::Polygon(points) => {
+            self.u8(3);
+            self.points(points);
+         }
+         ObjectShape::Polyline(points) => {
+            self.u8(4);
+            self.points(points);
+         }
+      }
+      self.bool(object.text.is_some());
+      if let Some(text) = &object.text {
+         self.str(&text.font_family);
+         self.u8(match text.h_align {
+            TextHAlign::Left => 0,
+            TextHAlign::Center => 1,
+            TextHAlign::Right => 2,
+         });
+         self.u32(text.pixel_size);
+         self.str(&text.text);
+      }
+   }
+
+   fn points(&mut self, points: &[Vec2<f32>]) {
+      self.len(points.len());
+      for &point in points {
+         self.vec2(point);
+      }
+   }
+
+   fn properties(&mut self, properties: &tiled::Properties) {
+      // Like chunks, properties are sorted to keep the output stable.
+      let mut properties: Vec<_> = properties.iter().collect();
+      properties.sort_unstable_by_key(|&(name, _)| name);
+      self.len(properties.len());
+      for (name, value) in properties {
+         self.str(name);
+         self.property_value(value);
+      }
+   }
+
+   fn property_value(&mut self, value: &PropertyValue) {
+      match value {
+         PropertyValue::String(value) => {
+            self.u8(0);
+            self.str(value);
+         }
+         PropertyValue::Int(value) => {
+            self.u8(1);
+            self.i32(*value);
+         }
+         PropertyValue::Float(value) => {
+            self.u8(2);
+            self.f32(*value);
+         }
+         PropertyValue::Bool(value) => {
+            self.u8(3);
+            self.bool(*value);
+         }
+         PropertyValue::Color(color) => {
+            self.u8(4);
+            self.bytes.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+         }
+         PropertyValue::File(path) => {
+            self.u8(5);
+            self.str(path);
+         }
+         PropertyValue::Object(id) => {
+            self.u8(6);
+            self.u32(*id);
+         }
+         PropertyValue::Class(members) => {
+            self.u8(7);
+            self.properties(members);
+         }
+         PropertyValue::Enum { enum_type, value } => {
+            self.u8(8);
+            self.str(enum_type);
+            match value {
+               EnumValue::Name(name) => {
+                  self.u8(0);
+                  self.str(name);
+               }
+               EnumValue::Int(value) => {
+                  self.u8(1);
+                  self.i32(*value);
+               }
+            }
+         }
+      }
+   }
+}
+
+/// Decodes values from the payload of a compiled map.
+struct Reader<'a> {
+   bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+   fn new(bytes: &'a [u8]) -> Self {
+      Self { bytes }
+   }
+
+   fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+      if len > self.bytes.len() {
+         anyhow::bail!("unexpected end of data");
+      }
+      let (taken, rest) = self.bytes.split_at(len);
+      self.bytes = rest;
+      Ok(taken)
+   }
+
+   fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+      Ok(self.take(N)?.try_into().unwrap())
+   }
+
+   fn u8(&mut self) -> anyhow::Result<u8> {
+      Ok(self.take(1)?[0])
+   }
+
+   fn u32(&mut self) -> anyhow::Result<u32> {
+      Ok(u32::from_le_bytes(self.array()?))
+   }
+
+   fn i32(&mut self) -> anyhow::Result<i32> {
+      Ok(i32::from_le_bytes(self.array()?))
+   }
+
+   fn f32(&mut self) -> anyhow::Result<f32> {
+      Ok(f32::from_le_bytes(self.array()?))
+   }
+
+   fn bool(&mut self) -> anyhow::Result<bool> {
+      match self.u8()? {
+         0 => Ok(false),
+         1 => Ok(true),
+         other => anyhow::bail!("invalid bool {}", other),
+      }
+   }
+
+   fn len(&mut self) -> anyhow::Result<usize> {
+      Ok(self.u32()? as usize)
+   }
+
+   /// Reads a list of values. The capacity reserved up front is limited by the amount of data
+   /// left, so that a corrupt length can't allocate huge amounts of memory.
+   fn list<T>(
+      &mut self,
+      mut read: impl FnMut(&mut Self) -> anyhow::Result<T>,
+   ) -> anyhow::Result<Vec<T>> {
+      let len = self.len()?;
+      let mut list = Vec::with_capacity(len.min(self.bytes.len()));
+      for _ in 0..len {
+         list.push(read(self)?);
+      }
+      Ok(list)
+   }
+
+   fn str(&mut self) -> anyhow::Result<String> {
+      let len = self.len()?;
+      String::from_utf8(self.take(len)?.to_vec()).context("invalid UTF-8 in string")
+   }
+
+   fn option_str(&mut self) -> anyhow::Result<Option<String>> {
+      Ok(if self.bool()? {
+         Some(self.str()?)
+      } else {
+         None
+      })
+   }
+
+   fn path(&mut self) -> anyhow::Result<PathBuf> {
+      Ok(PathBuf::from(self.str()?))
+   }
+
+   fn vec2(&mut self) -> anyhow::Result<Vec2<f32>> {
+      Ok(vector(self.f32()?, self.f32()?))
+   }
+
+   fn map(&mut self, source_checksum: u32) -> anyhow::Result<CompiledMap> {
+      let settings = self.settings().context("invalid map settings")?;
+      let tileset = self.tileset().context("invalid tileset")?;
+      let sources = self.list(Self::path)?;
+      let layers = self.list(|reader| reader.layer(&tileset))?;
+      Ok(CompiledMap {
+         settings,
+         tileset,
+         sources,
+         source_checksum,
+         layers,
+      })
+   }
+
+   /// Reads the map's settings, checking them the same way [`MapSettings::from_map`] does.
+   fn settings(&mut self) -> anyhow::Result<MapSettings> {
+      let settings = MapSettings {
+         gravity: self.vec2()?,
+         palette: self.len()?,
+         name: self.option_str()?,
+         music: self.option_str()?,
+         tile_size: self.vec2()?,
+      };
+      if settings.palette >= Zones::MAX {
+         anyhow::bail!("palette {} is out of range", settings.palette);
+      }
+      if !(settings.tile_size.x > 0.0 && settings.tile_size.y > 0.0) {
+         anyhow::bail!("invalid tile size {}", settings.tile_size);
+      }
+      Ok(settings)
+   }
+
+   fn tileset(&mut self) -> anyhow::Result<Tileset> {
+      let len = self.len()?;
+      if len == 0 || len > CompiledMap::MAX_TILES {
+         anyhow::bail!("invalid tile count {}", len);
+      }
+      let mut kinds = vec![TileKind::Empty; len];
+      for _ in 0..self.len()? {
+         let id = self.len()?;
+         let name = self.str()?;
+         let kind = TileKind::from_str(&name)
+            .with_context(|| format!("invalid tile kind {:?} of tile {}", name, id))?;
+         *kinds.get_mut(id).ok_or_else(|| anyhow::anyhow!("tile {} is out of bounds", id))? = kind;
+      }
+      if kinds[0] != TileKind::Empty {
+         anyhow::bail!("tile 0 must be empty");
+      }
+      let barrier_groups =
+         self.list(|reader| Ok((reader.u32()?, reader.u32()?)))?.into_iter().collect();
+      Ok(Tileset {
+         kinds,
+         barrier_groups,
+         untyped: HashSet::new(),
+      })
+   }
+
+   fn layer(&mut self, tileset: &Tileset) -> anyhow::Result<CompiledLayer> {
+      let name = self.str()?;
+      let visible = self.bool()?;
+      let opacity = self.f32()?;
+      let offset = self.vec2()?;
+      let parallax = self.vec2()?;
+      let properties = self.properties()?;
+      let kind = match self.u8()? {
+         0 => {
+            let chunks = self.list(|reader| reader.chunk(tileset))?.into_iter().collect();
+            let solids = self.list(|reader| {
+               Ok(SolidRect {
+                  left: reader.i32()?,
+                  top: reader.i32()?,
+                  right: reader.i32()?,
+                  bottom: reader.i32()?,
+                  group: reader.u32()?,
+               })
+            })?;
+            CompiledLayerKind::Tile { chunks, solids }
+         }
+         1 => CompiledLayerKind::Image(tiled::ImageLayer {
+            image: self.path()?,
+            repeat_x: self.bool()?,
+            repeat_y: self.bool()?,
+         }),
+         2 => CompiledLayerKind::Object(self.list(Self::object)?),
+         other => anyhow::bail!("invalid layer kind {}", other),
+      };
+      Ok(CompiledLayer {
+         name,
+         visible,
+         opacity,
+         offset,
+         parallax,
+         properties,
+         kind,
+      })
+   }
+
+   fn chunk(&mut self, tileset: &Tileset) -> anyhow::Result<((i32, i32), Chunk)> {
+      let position = (self.i32()?, self.i32()?);
+      let mut chunk = Chunk::from_tile(Tile::EMPTY);
+      for tile in &mut chunk.tiles {
+         let id = self.u32()?;
+         if !tileset.contains(id) {
+            anyhow::bail!("tile ID {} does not belong to the tileset", id);
+         }
+         let flip = self.u8()?;
+         let flip = TileFlip::from_bits(flip)
+            .ok_or_else(|| anyhow::anyhow!("invalid tile flip {}", flip))?;
+         *tile = Tile { id, flip };
+      }
+      Ok((position, chunk))
+   }
+
+   fn object(&mut self) -> anyhow::Result<tiled::Object> {
+      Ok(tiled::Object {
+         id: self.u32()?,
+         name: Some(self.str()?),
+         kind: Some(self.str()?),
+         x: self.f32()?,
+         y: self.f32()?,
+         width: Some(self.f32()?),
+         height: Some(self.f32()?),
+         rotation: Some(self.f32()?),
+         properties: self.properties()?,
+         shape: Some(match self.u8()? {
+            0 => ObjectShape::Rectangle,
+            1 => ObjectShape::Ellipse,
+            2 => ObjectShape::Point,
+            3 => ObjectShape::Polygon(self.list(Self::vec2)?),
+            4 => ObjectShape::Polyline(self.list(Self::vec2)?),
+            other => anyhow::bail!("invalid object shape {}", other),
+         }),
+         text: if self.bool()? {
+            Some(tiled::Text {
+               font_family: self.str()?,
+               h_align: match self.u8()? {
+                  0 => TextHAlign::Left,
+                  1 => TextHAlign::Center,
+                  2 => TextHAlign::Right,
+                  other => anyhow::bail!("invalid text alignment {}", other),
+               },
+               pixel_size: self.u32()?,
+               text: self.str()?,
+            })
+         } else {
+            None
+         },
+         template: None,
+      })
+   }
+
+   fn properties(&mut self) -> anyhow::Result<tiled::Properties> {
+      Ok(self.list(|reader| Ok((reader.str()?, reader.property_value()?)))?.into_iter().collect())
+   }
+
+   fn property_value(&mut self) -> anyhow::Result<PropertyValue> {
+      Ok(match self.u8()? {
+         0 => PropertyValue::String(self.str()?),
+         1 => PropertyValue::Int(self.i32()?),
+         2 => PropertyValue::Float(self.f32()?),
+         3 => PropertyValue::Bool(self.bool()?),
+         4 => PropertyValue::Color(Rgba::from(self.array::<4>()?)),
+         5 => PropertyValue::File(self.str()?),
+         6 => PropertyValue::Object(self.u32()?),
+         7 => PropertyValue::Class(self.properties()?),
+         8 => PropertyValue::Enum {
+            enum_type: self.str()?,
+            value: match self.u8()? {
+               0 => EnumValue::Name(self.str()?),
+               1 => EnumValue::Int(self.i32()?),
+               other => anyhow::bail!("invalid enum value kind {}", other),
+            },
+         },
+         other => anyhow::bail!("invalid property type {}", other),
+      })
+   }
+}
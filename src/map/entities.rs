@@ -4,29 +4,31 @@ use std::str::FromStr;
 
 use anyhow::Context;
 use hecs::{Entity, World};
-use log::error;
 use serde::de::IntoDeserializer;
 use serde::Deserialize;
-use vek::Mat2;
+use vek::{Mat2, Vec2};
 
 use crate::assets::FontFamily;
 use crate::common::{rect, vector, Rect};
 use crate::entities::camera::CameraView;
 use crate::entities::checkpoint::Checkpoint;
-use crate::entities::colliders::RectCollider;
+use crate::entities::colliders::StaticCollider;
+use crate::entities::out_of_bounds::{OutOfBounds, OutOfBoundsAction};
 use crate::entities::player::Player;
+use crate::entities::shapes::Shape;
 use crate::entities::text::Text;
 use crate::entities::trigger::Trigger;
 use crate::entities::zones::{DeadlyZone, PlatformerZone, ZoneData, ZoneSpawn, Zones};
+use crate::entities::{Name, Position, Size};
 use crate::physics::Physics;
 use crate::tiled::{self, PropertyValue};
 
-use super::{Layer, Loader, Map};
+use super::{Diagnostic, LayerKind, Loader, Map};
 
 /// Viable entity kinds, as stored in the map.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "snake_case")]
-enum EntityKind {
+pub(super) enum EntityKind {
    Player,
    Text,
 
@@ -34,6 +36,7 @@ enum EntityKind {
    CameraView,
    Trigger,
    Checkpoint,
+   OutOfBounds,
 
    ZonePlatformer,
    ZoneDeadly,
@@ -48,24 +51,27 @@ impl FromStr for EntityKind {
 }
 
 impl Loader {
-   /// Creates an object layer.
+   /// Creates an object layer. Objects that can't be spawned are skipped, and the problems with
+   /// them are collected into the loader's diagnostics.
    pub(super) fn create_object_layer(
       &mut self,
+      layer_name: &str,
       objects: Vec<tiled::Object>,
       world: &mut World,
       physics: &mut Physics,
-   ) -> Layer {
+   ) -> LayerKind {
       for object in objects {
-         let id = object.id;
-         if let Ok(kind) = EntityKind::from_str(&object.kind) {
-            if let Err(error) = self.spawn_entity(kind, object, world, physics) {
-               error!("object {}: {}", id, error);
-            }
-         } else {
-            error!("object {} of unknown kind {:?}", object.id, &object.kind);
+         let result = match EntityKind::from_str(object.kind()) {
+            Ok(kind) => self.spawn_entity(kind, object.clone(), world, physics),
+            Err(_) => Err(anyhow::anyhow!("unknown object kind {:?}", object.kind())),
+         };
+         if let Err(error) = result {
+            self.diagnostics.push(
+               Diagnostic::new(format!("{:#}", error)).in_layer(layer_name).on_object(&object),
+            );
          }
       }
-      Layer::Object
+      LayerKind::Object
    }
 
    /// Spawns an entity of the given kind into the world.
@@ -76,31 +82,32 @@ impl Loader {
       world: &mut World,
       physics: &mut Physics,
    ) -> anyhow::Result<()> {
-      let entity = self.entity(world, data.id);
-      let data = tiled::Object {
-         x: data.x / Map::tile_size().x,
-         y: data.y / Map::tile_size().y,
-         width: data.width / Map::tile_size().x,
-         height: data.height / Map::tile_size().y,
-         rotation: data.rotation / 180.0 * std::f32::consts::PI,
-         ..data
-      };
+      let id = data.id;
+      let entity = self.entity(world, id);
+      let mut data = data.in_world_units(self.tile_size);
+      let name = data.name.take().unwrap_or_default();
       let position = vector(data.x, data.y);
       let rect = data.rect();
       match kind {
          EntityKind::Player => Player::spawn(world, physics, entity, position),
          EntityKind::Text => Self::spawn_text(data, world, entity)?,
 
-         EntityKind::Collider => Self::spawn_collider(&data, world, physics, entity),
+         EntityKind::Collider => Self::spawn_collider(&data, world, physics, entity)?,
          EntityKind::CameraView => CameraView::spawn(world, physics, entity, rect),
          EntityKind::Trigger => self.spawn_trigger(&data, world, physics, entity)?,
          EntityKind::Checkpoint => Checkpoint::spawn(world, entity, position),
+         EntityKind::OutOfBounds => Self::spawn_out_of_bounds(&data, world, entity)?,
 
-         EntityKind::ZoneDeadly => Self::spawn_zone(&data, world, physics, entity, DeadlyZone),
+         EntityKind::ZoneDeadly => Self::spawn_zone(&data, world, physics, entity, DeadlyZone)?,
          EntityKind::ZonePlatformer => {
-            Self::spawn_zone(&data, world, physics, entity, PlatformerZone)
+            Self::spawn_zone(&data, world, physics, entity, PlatformerZone)?
          }
       }
+      if !name.is_empty() {
+         world.insert_one(entity, Name(name.clone()))?;
+         self.objects.insert_name(name, entity);
+      }
+      self.objects.mark_spawned(id);
 
       Ok(())
    }
@@ -132,8 +139,9 @@ impl Loader {
       world: &mut World,
       physics: &mut Physics,
       entity: Entity,
-   ) {
-      RectCollider::spawn(world, physics, entity, data.rect());
+   ) -> anyhow::Result<()> {
+      let (position, shape) = data.shape()?;
+      StaticCollider::spawn(world, physics, entity, position, data.rotation(), shape)
    }
 
    /// Spawns a zone of the given kind.
@@ -143,13 +151,36 @@ impl Loader {
       physics: &mut Physics,
       entity: Entity,
       kind: impl ZoneData + ZoneSpawn,
-   ) {
-      let top_left = vector(data.x, data.y);
-      let size = vector(data.width, data.height);
-      let center_offset = size / 2.0;
-      let rotation = Mat2::rotation_z(data.rotation);
-      let center = top_left + rotation * center_offset;
-      Zones::spawn(world, physics, entity, kind, center, size, data.rotation);
+   ) -> anyhow::Result<()> {
+      let (position, shape) = data.shape()?;
+      Zones::spawn(
+         world,
+         physics,
+         entity,
+         kind,
+         position,
+         data.rotation(),
+         shape,
+      )
+   }
+
+   /// Spawns an out-of-bounds region. The `action` property determines what happens to players
+   /// that enter it, and defaults to killing them.
+   fn spawn_out_of_bounds(
+      data: &tiled::Object,
+      world: &mut World,
+      entity: Entity,
+   ) -> anyhow::Result<()> {
+      let action = match data.properties.get("action") {
+         Some(action) => {
+            let action =
+               action.as_str().ok_or_else(|| anyhow::anyhow!("'action' field must be a string"))?;
+            OutOfBoundsAction::from_str(action).context("invalid out of bounds action")?
+         }
+         None => OutOfBoundsAction::Kill,
+      };
+      OutOfBounds::spawn(world, entity, data.rect(), action);
+      Ok(())
    }
 
    fn spawn_trigger(
@@ -159,7 +190,6 @@ impl Loader {
       physics: &mut Physics,
       entity: Entity,
    ) -> anyhow::Result<()> {
-      let rect = data.rect();
       let target = data
          .properties
          .get("trigger")
@@ -175,18 +205,80 @@ impl Loader {
          .as_int()
          .ok_or_else(|| anyhow::anyhow!("'method' field must be an int"))?
          as u32;
-      Ok(Trigger::spawn(
+      let (position, shape) = data.shape()?;
+      Trigger::spawn(
          world,
          physics,
          entity,
-         rect,
+         position,
+         data.rotation(),
+         shape,
          Trigger::new(target, method),
-      ))
+      )
+   }
+}
+
+impl Map {
+   /// The distance the playable area extends past the map's tiles and camera views.
+   const FALLBACK_BOUNDS_MARGIN: f32 = 4.0;
+
+   /// Spawns the map-wide playable area, which covers all of the map's tiles and camera views.
+   /// This catches players that leave the map through places no out-of-bounds region covers.
+   pub(super) fn spawn_fallback_bounds(&self, world: &mut World) {
+      let tiles = self.layers.iter().filter_map(|layer| match &layer.kind {
+         LayerKind::Tile(tiles) => tiles.bounds(),
+         LayerKind::Image(_) | LayerKind::Object => None,
+      });
+      let camera_views: Vec<_> = world
+         .query_mut::<(&CameraView, &Position, &Size)>()
+         .into_iter()
+         .map(|(_id, (_, &Position(position), &Size(size)))| rect(position, size))
+         .collect();
+      if let Some(bounds) = tiles.chain(camera_views).reduce(|a, b| a.combine(&b)) {
+         let margin = Self::FALLBACK_BOUNDS_MARGIN;
+         let bounds = Rect::new(
+            bounds.x - margin,
+            bounds.y - margin,
+            bounds.width + margin * 2.0,
+            bounds.height + margin * 2.0,
+         );
+         OutOfBounds::spawn_playable_area(world, world.reserve_entity(), bounds);
+      }
    }
 }
 
 impl tiled::Object {
-   fn rect(&self) -> Rect {
-      rect(vector(self.x, self.y), vector(self.width, self.height))
+   /// Converts the object's position, size, and shape from pixels to world units, and its
+   /// rotation from degrees to radians.
+   pub(super) fn in_world_units(self, tile_size: Vec2<f32>) -> Self {
+      Self {
+         x: self.x / tile_size.x,
+         y: self.y / tile_size.y,
+         width: self.width.map(|width| width / tile_size.x),
+         height: self.height.map(|height| height / tile_size.y),
+         rotation: self.rotation.map(|rotation| rotation / 180.0 * std::f32::consts::PI),
+         shape: self.shape.map(|shape| shape.scaled(vector(1.0, 1.0) / tile_size)),
+         ..self
+      }
+   }
+
+   pub(super) fn rect(&self) -> Rect {
+      rect(vector(self.x, self.y), self.size())
+   }
+
+   /// Returns the object's shape, along with the position the shape is relative to. Rectangles
+   /// and ellipses are positioned at their (rotated) centers, while polygons and polylines are
+   /// positioned at the object's origin.
+   pub(super) fn shape(&self) -> anyhow::Result<(Vec2<f32>, Shape)> {
+      let origin = vector(self.x, self.y);
+      let size = self.size();
+      let center = origin + Mat2::rotation_z(self.rotation()) * (size / 2.0);
+      Ok(match &self.shape {
+         None | Some(tiled::ObjectShape::Rectangle) => (center, Shape::Rectangle(size)),
+         Some(tiled::ObjectShape::Ellipse) => (center, Shape::Ellipse(size)),
+         Some(tiled::ObjectShape::Polygon(points)) => (origin, Shape::Polygon(points.clone())),
+         Some(tiled::ObjectShape::Polyline(points)) => (origin, Shape::Polyline(points.clone())),
+         Some(tiled::ObjectShape::Point) => anyhow::bail!("point objects do not have a shape"),
+      })
    }
 }
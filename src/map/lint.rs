@@ -0,0 +1,488 @@
+//! Checking maps for problems.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+
+use hecs::World;
+use log::{error, warn};
+use vek::Vec2;
+
+use crate::common::{vector, Rect};
+use crate::entities::shapes::Shape;
+use crate::physics::Physics;
+use crate::tiled::{self, ObjectId};
+
+use super::entities::EntityKind;
+use super::tiles::TileKind;
+use super::{Loader, Map, MapSettings, ObjectRegistry, Tileset};
+
+/// How serious a problem found in a map is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+   /// The map can be played, but the problem makes it unpleasant to play.
+   Warning,
+   /// The map cannot be loaded, or parts of it don't work.
+   Error,
+}
+
+/// A problem found in a map, along with where in the map it is.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+   pub severity: Severity,
+   /// The name of the layer the problem is in.
+   pub layer: Option<String>,
+   /// The ID and name of the object the problem is with.
+   pub object: Option<(ObjectId, String)>,
+   /// The position of the problem in the map, in pixels.
+   pub position: Option<Vec2<f32>>,
+   pub message: String,
+}
+
+impl Diagnostic {
+   /// Creates an error that isn't tied to any particular place in the map.
+   pub(super) fn new(message: impl Display) -> Self {
+      Self {
+         severity: Severity::Error,
+         layer: None,
+         object: None,
+         position: None,
+         message: message.to_string(),
+      }
+   }
+
+   /// Turns the diagnostic into a warning.
+   pub(super) fn warning(self) -> Self {
+      Self {
+         severity: Severity::Warning,
+         ..self
+      }
+   }
+
+   /// Returns whether the diagnostic is an error.
+   pub fn is_error(&self) -> bool {
+      self.severity == Severity::Error
+   }
+
+   /// Logs the diagnostic at the level matching its severity.
+   pub fn log(&self) {
+      match self.severity {
+         Severity::Warning => warn!("{}", self),
+         Severity::Error => error!("{}", self),
+      }
+   }
+
+   /// Places the diagnostic in the given layer.
+   pub(super) fn in_layer(self, layer: &str) -> Self {
+      Self {
+         layer: Some(layer.to_owned()),
+         ..self
+      }
+   }
+
+   /// Places the diagnostic at the given position, in pixels.
+   pub(super) fn at(self, position: Vec2<f32>) -> Self {
+      Self {
+         position: Some(position),
+         ..self
+      }
+   }
+
+   /// Places the diagnostic on the given object. The object's position must be in pixels.
+   pub(super) fn on_object(self, object: &tiled::Object) -> Self {
+      Self {
+         object: Some((object.id, object.name().to_owned())),
+         ..self
+      }
+      .at(vector(object.x, object.y))
+   }
+}
+
+impl Display for Diagnostic {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      let mut location = Vec::new();
+      if let Some(layer) = &self.layer {
+         location.push(format!("layer {:?}", layer));
+      }
+      match &self.object {
+         Some((id, name)) if !name.is_empty() => location.push(format!("object {} {:?}", id, name)),
+         Some((id, _)) => location.push(format!("object {}", id)),
+         None => (),
+      }
+      if let Some(position) = self.position {
+         location.push(format!("at ({}, {})", position.x, position.y));
+      }
+      if location.is_empty() {
+         write!(f, "{}", self.message)
+      } else {
+         write!(f, "{}: {}", location.join(", "), self.message)
+      }
+   }
+}
+
+impl Map {
+   /// Checks the map at the given path for problems, without loading it into the game. Unlike
+   /// loading, checking doesn't stop at the first problem, and also reports problems that make the
+   /// map unpleasant to play rather than impossible to load. An error is only returned if the map
+   /// cannot be read at all.
+   pub fn check(path: &Path) -> anyhow::Result<Vec<Diagnostic>> {
+      let map = tiled::Map::load(path)?;
+      let mut linter = Linter {
+         tile_size: vector(map.tile_width.max(1) as f32, map.tile_height.max(1) as f32),
+         diagnostics: Vec::new(),
+      };
+      linter.check(map);
+      Ok(linter.diagnostics)
+   }
+}
+
+/// The tiles of all of a map's tile layers, combined.
+#[derive(Default)]
+struct Terrain {
+   /// The positions of tiles that aren't empty.
+   occupied: HashSet<(i32, i32)>,
+   /// The positions of tiles the player can stand on, along with the names of the layers they're
+   /// in.
+   floors: HashMap<(i32, i32), String>,
+}
+
+/// Map checking state.
+struct Linter {
+   tile_size: Vec2<f32>,
+   diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+   /// Checks the whole map.
+   fn check(&mut self, map: tiled::Map) {
+      let gravity = match MapSettings::from_map(&map) {
+         Ok(settings) => settings.gravity,
+         Err(error) => {
+            self.diagnostics.push(Diagnostic::new(format!(
+               "invalid map properties: {:#}",
+               error
+            )));
+            MapSettings::DEFAULT_GRAVITY
+         }
+      };
+      let tileset = self.check_tilesets(&map.tilesets);
+
+      // Objects are spawned into a scratch world, so that everything the loader would complain
+      // about is caught without having to duplicate it here.
+      let mut loader = Loader {
+         objects: ObjectRegistry::default(),
+         tile_size: self.tile_size,
+         diagnostics: Vec::new(),
+      };
+      let mut world = World::new();
+      let mut physics = Physics::new(gravity);
+
+      let mut objects = Vec::new();
+      let mut terrain = Terrain::default();
+      for layer in map.layers {
+         match layer.kind {
+            tiled::LayerKind::Tile(tiles) => {
+               if let Some(tileset) = &tileset {
+                  self.check_tile_layer(&layer.name, &tiles, tileset, &mut terrain);
+               }
+            }
+            tiled::LayerKind::Object {
+               objects: mut layer_objects,
+            } => {
+               for object in &mut layer_objects {
+                  object.x += layer.offset_x;
+                  object.y += layer.offset_y;
+               }
+               loader.create_object_layer(
+                  &layer.name,
+                  layer_objects.clone(),
+                  &mut world,
+                  &mut physics,
+               );
+               objects.extend(layer_objects.into_iter().map(|object| (layer.name.clone(), object)));
+            }
+            tiled::LayerKind::Image(image) => {
+               if !image.image.as_os_str().is_empty() && !image.image.is_file() {
+                  self.diagnostics.push(
+                     Diagnostic::new(format!("image {:?} does not exist", image.image))
+                        .in_layer(&layer.name),
+                  );
+               }
+            }
+            tiled::LayerKind::Group { .. } => {
+               unreachable!("group layers are flattened when the map is loaded")
+            }
+         }
+      }
+      self.diagnostics.append(&mut loader.diagnostics);
+
+      self.check_objects(&objects);
+      self.check_camera_coverage(&objects, &terrain);
+   }
+
+   /// Checks the types of all tiles in the tilesets, and combines the tilesets if they're valid.
+   fn check_tilesets(&mut self, tilesets: &[tiled::MapTileset]) -> Option<Tileset> {
+      let mut tiles_valid = true;
+      for tiled::MapTileset {
+         first_gid, tileset, ..
+      } in tilesets
+      {
+         for tile in &tileset.tiles {
+            if tile.kind.is_empty() {
+               continue;
+            }
+            let problem = match TileKind::from_str(&tile.kind) {
+               Ok(TileKind::Barrier) => Tileset::parse_barrier_group(&tile.properties)
+                  .err()
+                  .map(|error| format!("invalid barrier: {:#}", error)),
+               Ok(_) => None,
+               Err(_) => Some(format!("unknown tile type {:?}", tile.kind)),
+            };
+            if let Some(problem) = problem {
+               tiles_valid = false;
+               self.diagnostics.push(Diagnostic::new(format!(
+                  "tile {} of tileset with firstgid {}: {}",
+                  tile.id, first_gid, problem
+               )));
+            }
+         }
+      }
+      match Tileset::try_from(tilesets.to_vec()) {
+         Ok(tileset) => Some(tileset),
+         Err(error) => {
+            // Problems with individual tiles have already been reported above.
+            if tiles_valid {
+               self.diagnostics.push(Diagnostic::new(format!("{:#}", error)));
+            }
+            None
+         }
+      }
+   }
+
+   /// Checks that the tiles in a tile layer belong to the map's tilesets, and adds them to the
+   /// terrain.
+   fn check_tile_layer(
+      &mut self,
+      layer_name: &str,
+      tiles: &tiled::TileLayer,
+      tileset: &Tileset,
+      terrain: &mut Terrain,
+   ) {
+      for (position, gid) in tiles.tiles() {
+         let id = gid.tile_id();
+         if !tileset.contains(id) {
+            self.diagnostics.push(
+               Diagnostic::new(format!("tile ID {} does not belong to any tileset", id))
+                  .in_layer(layer_name)
+                  .at(self.tile_to_pixels(position)),
+            );
+            continue;
+         }
+         if !tileset.has_type(id) {
+            self.diagnostics.push(
+               Diagnostic::new(format!(
+                  "tile ID {} does not have a type, so it cannot be drawn",
+                  id
+               ))
+               .in_layer(layer_name)
+               .at(self.tile_to_pixels(position)),
+            );
+            continue;
+         }
+         let kind = tileset.kind(id);
+         if kind != TileKind::Empty {
+            terrain.occupied.insert(position);
+         }
+         if kind.is_solid() || kind == TileKind::Barrier || kind.slope_line().is_some() {
+            terrain.floors.entry(position).or_insert_with(|| layer_name.to_owned());
+         }
+      }
+   }
+
+   /// Checks the relationships between objects, and the shapes of objects.
+   fn check_objects(&mut self, objects: &[(String, tiled::Object)]) {
+      let ids: HashSet<_> = objects.iter().map(|(_, object)| object.id).collect();
+      let mut triggered = HashSet::new();
+      let mut players = 0;
+      let mut camera_views: Vec<(ObjectId, Rect)> = Vec::new();
+
+      for (layer, object) in objects {
+         let kind = match kind_of(object) {
+            Some(kind) => kind,
+            // Unknown kinds are already reported by the loader.
+            None => continue,
+         };
+         let report = |message: String| Diagnostic::new(message).in_layer(layer).on_object(object);
+         let world_object = object.clone().in_world_units(self.tile_size);
+
+         match kind {
+            EntityKind::Player => {
+               players += 1;
+               if players > 1 {
+                  self.diagnostics.push(report("the map has more than one player".into()));
+               }
+            }
+            EntityKind::Trigger => {
+               if let Some(target) = object.properties.get("trigger").and_then(|t| t.as_object()) {
+                  if ids.contains(&target) {
+                     triggered.insert(target);
+                  } else {
+                     self
+                        .diagnostics
+                        .push(report(format!("trigger target {} does not exist", target)));
+                  }
+               }
+            }
+            EntityKind::CameraView => {
+               let rect = world_object.rect();
+               for &(other_id, other_rect) in &camera_views {
+                  if rect.intersects(&other_rect) {
+                     self.diagnostics.push(
+                        report(format!(
+                           "camera view overlaps with camera view {}",
+                           other_id
+                        ))
+                        .warning(),
+                     );
+                  }
+               }
+               camera_views.push((object.id, rect));
+            }
+            _ => (),
+         }
+
+         let has_zero_size = match kind {
+            EntityKind::CameraView | EntityKind::OutOfBounds => {
+               let size = world_object.size();
+               size.x <= 0.0 || size.y <= 0.0
+            }
+            EntityKind::Collider
+            | EntityKind::Trigger
+            | EntityKind::ZonePlatformer
+            | EntityKind::ZoneDeadly => match world_object.shape() {
+               // Polygons and polylines are checked when their colliders are built.
+               Ok((_, Shape::Rectangle(size) | Shape::Ellipse(size))) => {
+                  size.x <= 0.0 || size.y <= 0.0
+               }
+               _ => false,
+            },
+            _ => false,
+         };
+         if has_zero_size {
+            self.diagnostics.push(report(format!("{} has zero size", object.kind())));
+         }
+      }
+
+      if players == 0 {
+         self.diagnostics.push(Diagnostic::new("the map does not have a player"));
+      }
+      // This is only a warning, because a checkpoint that's never triggered is harmless, and the
+      // one at the start of the level doesn't need a trigger anyway.
+      for (layer, object) in objects {
+         if kind_of(object) == Some(EntityKind::Checkpoint) && !triggered.contains(&object.id) {
+            self.diagnostics.push(
+               Diagnostic::new("checkpoint is not the target of any trigger")
+                  .warning()
+                  .in_layer(layer)
+                  .on_object(object),
+            );
+         }
+      }
+   }
+
+   /// Checks that the player starts inside a camera view, and that every place the player can
+   /// stand at is covered by a camera view.
+   ///
+   /// Only places inside the playable part of the map are checked: the bounding box of all camera
+   /// views, minus the out-of-bounds regions. Everything else, such as the tops of the walls
+   /// surrounding the level, can't be reached without dying.
+   fn check_camera_coverage(&mut self, objects: &[(String, tiled::Object)], terrain: &Terrain) {
+      let rects_of = |kind: EntityKind| -> Vec<Rect> {
+         objects
+            .iter()
+            .filter(|(_, object)| kind_of(object) == Some(kind))
+            .map(|(_, object)| object.clone().in_world_units(self.tile_size).rect())
+            .collect()
+      };
+      let camera_views = rects_of(EntityKind::CameraView);
+      let out_of_bounds = rects_of(EntityKind::OutOfBounds);
+      if camera_views.is_empty() {
+         self.diagnostics.push(Diagnostic::new("the map does not have any camera views"));
+         return;
+      }
+      let is_covered =
+         |point: Vec2<f32>| camera_views.iter().any(|view| view.contains_point(point));
+      let playable_area = camera_views.iter().copied().reduce(|a, b| a.combine(&b)).unwrap();
+      let is_playable = |point: Vec2<f32>| {
+         playable_area.contains_point(point)
+            && !out_of_bounds.iter().any(|region| region.contains_point(point))
+      };
+
+      for (layer, object) in
+         objects.iter().filter(|(_, object)| kind_of(object) == Some(EntityKind::Player))
+      {
+         let position = vector(object.x, object.y) / self.tile_size;
+         if !is_covered(position) {
+            self.diagnostics.push(
+               Diagnostic::new("the player starts outside of all camera views")
+                  .warning()
+                  .in_layer(layer)
+                  .on_object(object),
+            );
+         }
+      }
+
+      // The player stands in the tile above the floor, so that's the tile that needs to be
+      // covered.
+      let mut uncovered: Vec<_> = terrain
+         .floors
+         .iter()
+         .filter(|&(&(x, y), _)| {
+            let standing = vector(x as f32 + 0.5, y as f32 - 0.5);
+            !terrain.occupied.contains(&(x, y - 1))
+               && is_playable(standing)
+               && !is_covered(standing)
+         })
+         .map(|(&(x, y), layer)| (layer, y, x))
+         .collect();
+      uncovered.sort();
+
+      // Report horizontal runs of uncovered floor together, so that a floor that's missing a
+      // camera view doesn't result in a problem for every single one of its tiles.
+      let mut runs: Vec<(&String, i32, i32, usize)> = Vec::new();
+      for (layer, y, x) in uncovered {
+         match runs.last_mut() {
+            Some((run_layer, run_y, run_x, length))
+               if *run_layer == layer && *run_y == y && *run_x + *length as i32 == x =>
+            {
+               *length += 1;
+            }
+            _ => runs.push((layer, y, x, 1)),
+         }
+      }
+      for (layer, y, x, length) in runs {
+         let message = if length == 1 {
+            "the player can stand on this tile, but no camera view covers it".to_owned()
+         } else {
+            format!(
+               "the player can stand on {} tiles starting here, but no camera view covers them",
+               length
+            )
+         };
+         self.diagnostics.push(
+            Diagnostic::new(message).warning().in_layer(layer).at(self.tile_to_pixels((x, y))),
+         );
+      }
+   }
+
+   /// Converts a tile position to a position in pixels.
+   fn tile_to_pixels(&self, (x, y): (i32, i32)) -> Vec2<f32> {
+      vector(x as f32, y as f32) * self.tile_size
+   }
+}
+
+/// Returns the kind of entity an object spawns, or `None` if the object's kind is unknown.
+fn kind_of(object: &tiled::Object) -> Option<EntityKind> {
+   EntityKind::from_str(object.kind()).ok()
+}
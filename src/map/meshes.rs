@@ -1,12 +1,13 @@
 //! Map tile meshes.
 
-use tetra::math::Vec2;
+use vek::Vec2;
 
 use crate::assets::RemappableColors;
 use crate::common::{colored_vertex, rect, vector, Axis, Rect};
 use crate::meshes::MeshBuilder;
+use crate::tiled::TileFlip;
 
-use super::tiles::{Corner, Side, Sides};
+use super::tiles::{Corner, Side, Sides, SlopeLine};
 
 pub struct TileMeshes;
 
@@ -49,6 +50,20 @@ impl TileMeshes {
       }
    }
 
+   /// Builds the mesh for a barrier. Barriers are drawn in the accent color, with a dot in the
+   /// middle, to set them apart from solid blocks.
+   pub fn build_barrier(builder: &mut MeshBuilder, position: Vec2<f32>) {
+      for side in [Side::Top, Side::Left, Side::Bottom, Side::Right] {
+         let rect = Self::side_rect(position, side);
+         builder.rectangle(rect, RemappableColors::ACCENT);
+      }
+      let dot_size = vector(Self::THICKNESS, Self::THICKNESS) * 2.0;
+      builder.rectangle(
+         rect(position - dot_size / 2.0, dot_size),
+         RemappableColors::ACCENT,
+      );
+   }
+
    /// Builds the mesh for corners.
    pub fn build_corner(builder: &mut MeshBuilder, position: Vec2<f32>, corner: Corner) {
       let size = vector(Self::THICKNESS, Self::THICKNESS);
@@ -97,6 +112,18 @@ impl TileMeshes {
       );
    }
 
+   /// Builds the mesh for the surface of a slope.
+   pub fn build_slope(
+      builder: &mut MeshBuilder,
+      position: Vec2<f32>,
+      line: SlopeLine,
+      flip: TileFlip,
+   ) {
+      let points: Vec<_> =
+         line.polygon(Self::THICKNESS, flip).into_iter().map(|point| position + point).collect();
+      builder.convex_polygon(&points, RemappableColors::FOREGROUND);
+   }
+
    /// Builds spikes pointing at the given side.
    pub fn build_spikes(builder: &mut MeshBuilder, position: Vec2<f32>, side: Side) {
       let vertices = [
@@ -1,61 +1,191 @@
 //! Map loading, storage, and physics.
 
+mod compiled;
 mod entities;
+mod lint;
+#[cfg(feature = "game")]
 mod meshes;
+mod registry;
+#[cfg(feature = "game")]
 mod rendering;
+mod settings;
 mod tiles;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Index, IndexMut};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::Context;
+use anyhow::Context as AnyhowContext;
 use hecs::{Entity, World};
+#[cfg(feature = "game")]
+use log::error;
+use rapier2d::prelude::ColliderHandle;
+#[cfg(feature = "game")]
 use tetra::graphics::mesh::Mesh;
-use tetra::math::Vec2;
+#[cfg(feature = "game")]
+use tetra::graphics::{FilterMode, Texture};
+#[cfg(feature = "game")]
+use tetra::Context;
+use vek::Vec2;
 
+use crate::common::asset_path;
+#[cfg(feature = "game")]
 use crate::common::vector;
-use crate::physics::Physics;
-use crate::tiled::{self, ObjectId, TileId};
+use crate::entities::player::Morph;
+use crate::physics::{CollisionGroups, Physics};
+use crate::resources::Resources;
+use crate::tiled::{self, ObjectId, TileFlip};
 
+pub use compiled::CompiledMap;
+pub use lint::{Diagnostic, Severity};
+#[cfg(feature = "game")]
 pub use meshes::*;
+pub use registry::ObjectRegistry;
+pub use settings::MapSettings;
 
-use self::tiles::TileKind;
+use self::tiles::{Outline, SolidRect, TileKind};
 
-/// The tileset of a map.
+/// The tiles available in a map, combined from all of the map's tilesets.
 pub struct Tileset {
+   /// The kinds of tiles, indexed by global tile ID. The tile with global ID 0 is always
+   /// [`TileKind::Empty`].
    pub kinds: Vec<TileKind>,
+   /// The collision groups of [`TileKind::Barrier`] tiles, which determine which morphs the
+   /// barriers are solid to.
+   pub barrier_groups: HashMap<GlobalTileId, u32>,
+   /// The tiles that don't have a type. Their kind is [`TileKind::Empty`], but unlike tiles with
+   /// the type `empty` they can't be drawn, so placing them in the map is a mistake. This is not
+   /// stored in compiled maps, as their tiles have already been placed.
+   pub untyped: HashSet<GlobalTileId>,
 }
 
 impl Tileset {
-   /// Returns the kind of the given tile.
-   pub fn kind(&self, tile_id: TileId) -> TileKind {
+   /// Returns the kind of the tile with the given global ID.
+   pub fn kind(&self, tile_id: GlobalTileId) -> TileKind {
       self.kinds[tile_id as usize]
    }
+
+   /// Returns the collision groups of the barrier tile with the given global ID.
+   pub fn barrier_group(&self, tile_id: GlobalTileId) -> u32 {
+      self.barrier_groups.get(&tile_id).copied().unwrap_or(CollisionGroups::BARRIERS)
+   }
+
+   /// Parses the collision groups of a barrier from its tile's properties. The `blocks` property
+   /// lists the morphs (separated by commas) the barrier is solid to; if it's absent, the barrier
+   /// blocks all morphs.
+   fn parse_barrier_group(properties: &tiled::Properties) -> anyhow::Result<u32> {
+      let blocks = match properties.get("blocks") {
+         Some(blocks) => {
+            blocks.as_str().context("the 'blocks' property of a barrier must be a string")?
+         }
+         None => return Ok(CollisionGroups::BARRIERS),
+      };
+      blocks.split(',').map(str::trim).filter(|name| !name.is_empty()).try_fold(0, |group, name| {
+         let morph = Morph::from_str(name).with_context(|| format!("invalid morph {:?}", name))?;
+         Ok(group | morph.barrier_group())
+      })
+   }
+
+   /// Returns whether the tile with the given global ID has a type.
+   pub fn has_type(&self, tile_id: GlobalTileId) -> bool {
+      !self.untyped.contains(&tile_id)
+   }
+
+   /// Returns whether the tile with the given global ID belongs to any of the map's tilesets.
+   pub fn contains(&self, tile_id: GlobalTileId) -> bool {
+      (tile_id as usize) < self.kinds.len()
+   }
 }
 
-impl TryFrom<tiled::Tileset> for Tileset {
+impl TryFrom<Vec<tiled::MapTileset>> for Tileset {
    type Error = anyhow::Error;
 
-   fn try_from(data: tiled::Tileset) -> Result<Self, Self::Error> {
-      let n_tiles = data.tile_count as usize;
+   fn try_from(mut tilesets: Vec<tiled::MapTileset>) -> Result<Self, Self::Error> {
+      tilesets.sort_by_key(|tileset| tileset.first_gid);
       let mut set = Self {
-         kinds: Vec::from_iter(std::iter::repeat(TileKind::Empty).take(n_tiles)),
+         kinds: vec![TileKind::Empty],
+         barrier_groups: HashMap::new(),
+         untyped: HashSet::new(),
       };
 
-      for tile in data.tiles {
-         let id = tile.id as usize;
-         let kind = TileKind::from_str(&tile.kind).context("invalid tile type used")?;
-         set.kinds[id] = kind;
+      for tiled::MapTileset {
+         first_gid, tileset, ..
+      } in tilesets
+      {
+         if (first_gid as usize) < set.kinds.len() {
+            anyhow::bail!(
+               "tileset with firstgid {} overlaps with the tileset before it",
+               first_gid
+            );
+         }
+         let n_tiles = first_gid as usize + tileset.tile_count as usize;
+         set.kinds.resize(n_tiles, TileKind::Empty);
+         // Tiles without a type stay empty. They can't be drawn, so placing them in the map is
+         // reported when the map is loaded and checked.
+         set.untyped.extend(first_gid..n_tiles as GlobalTileId);
+         for tile in tileset.tiles {
+            if tile.kind.is_empty() {
+               continue;
+            }
+            let id = first_gid as usize + tile.id as usize;
+            set.untyped.remove(&(id as GlobalTileId));
+            let kind = TileKind::from_str(&tile.kind).with_context(|| {
+               format!(
+                  "invalid tile type used by tile {} of tileset with firstgid {}",
+                  tile.id, first_gid
+               )
+            })?;
+            *set.kinds.get_mut(id).ok_or_else(|| {
+               anyhow::anyhow!(
+                  "tile {} is out of bounds of tileset with firstgid {}",
+                  tile.id,
+                  first_gid
+               )
+            })? = kind;
+            if kind == TileKind::Barrier {
+               let group = Self::parse_barrier_group(&tile.properties).with_context(|| {
+                  format!(
+                     "invalid barrier tile {} in tileset with firstgid {}",
+                     tile.id, first_gid
+                  )
+               })?;
+               set.barrier_groups.insert(id as GlobalTileId, group);
+            }
+         }
       }
 
       Ok(set)
    }
 }
 
+/// A tile ID that is unique across all of a map's tilesets. This is the same as Tiled's global
+/// tile IDs, but without the flip flags.
+pub type GlobalTileId = u32;
+
+/// A tile placed in a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+   /// The global ID of the tile.
+   pub id: GlobalTileId,
+   /// How the tile is flipped.
+   pub flip: TileFlip,
+}
+
+impl Tile {
+   /// The empty tile.
+   pub const EMPTY: Self = Self {
+      id: 0,
+      flip: TileFlip::empty(),
+   };
+}
+
 /// A chunk of tiles.
 pub struct Chunk {
-   tiles: [TileId; Self::LENGTH],
+   tiles: [Tile; Self::LENGTH],
+   /// The outlines of auto-tiled solid blocks. Tiles of other kinds have empty outlines.
+   outlines: [Outline; Self::LENGTH],
+   #[cfg(feature = "game")]
    mesh: Option<Mesh>,
 }
 
@@ -65,23 +195,51 @@ impl Chunk {
    pub const SIZE: usize = 1 << Self::SIZE_BITS;
    const LENGTH: usize = Self::SIZE * Self::SIZE;
 
-   /// Creates a new chunk from a tile ID.
-   pub fn from_tile_id(id: TileId) -> Self {
+   /// Creates a new chunk filled with the given tile.
+   pub fn from_tile(tile: Tile) -> Self {
       Self {
-         tiles: [id; Self::LENGTH],
+         tiles: [tile; Self::LENGTH],
+         outlines: [Outline::EMPTY; Self::LENGTH],
+         #[cfg(feature = "game")]
          mesh: None,
       }
    }
 
+   /// Splits a tile position into the position of the chunk the tile belongs to, and the tile's
+   /// position within that chunk.
+   pub fn split_position((x, y): (i32, i32)) -> ((i32, i32), (usize, usize)) {
+      const MASK: i32 = Chunk::SIZE as i32 - 1;
+      (
+         (x >> Self::SIZE_BITS, y >> Self::SIZE_BITS),
+         ((x & MASK) as usize, (y & MASK) as usize),
+      )
+   }
+
+   /// Returns the outline of the auto-tiled solid block at the given position.
+   pub fn outline(&self, (x, y): (usize, usize)) -> Outline {
+      self.outlines[x + Self::SIZE * y]
+   }
+
+   /// Sets the outline of the auto-tiled solid block at the given position.
+   fn set_outline(&mut self, (x, y): (usize, usize), outline: Outline) {
+      self.outlines[x + Self::SIZE * y] = outline;
+   }
+
+   /// Discards the chunk's cached mesh, such that it's regenerated the next time it's drawn.
+   #[cfg(feature = "game")]
+   fn invalidate_mesh(&mut self) {
+      self.mesh = None;
+   }
+
    /// Checks whether the chunk is empty (all tiles in it are [`TileKind::Empty`]).
    pub fn is_empty(&self, tileset: &Tileset) -> bool {
-      self.tiles.iter().all(|&tile_id| tileset.kind(tile_id) == TileKind::Empty)
+      self.tiles.iter().all(|tile| tileset.kind(tile.id) == TileKind::Empty)
    }
 }
 
 /// Indexing for chunks, using `(X, Y)` coordinates.
 impl Index<(usize, usize)> for Chunk {
-   type Output = TileId;
+   type Output = Tile;
 
    fn index(&self, index: (usize, usize)) -> &Self::Output {
       &self.tiles[index.0 + Self::SIZE * index.1]
@@ -95,81 +253,294 @@ impl IndexMut<(usize, usize)> for Chunk {
    }
 }
 
-/// A layer.
-pub enum Layer {
-   Tile { chunks: HashMap<(i32, i32), Chunk> },
+/// A layer made out of tiles.
+pub struct TileLayer {
+   chunks: HashMap<(i32, i32), Chunk>,
+   /// The merged colliders of the layer's solid tiles and barriers, along with the rectangles
+   /// they cover.
+   solids: Vec<(SolidRect, ColliderHandle)>,
+   /// The colliders of individual tiles, such as spikes and slopes.
+   tile_colliders: HashMap<(i32, i32), ColliderHandle>,
+   /// The cached meshes of regions (square groups of chunks), used when drawing with
+   /// [`MeshBatching::Regions`]. Regions without any geometry map to `None`; regions missing from
+   /// the map are regenerated the next time they're drawn.
+   #[cfg(feature = "game")]
+   regions: HashMap<(i32, i32), Option<Mesh>>,
+}
+
+/// A layer made out of a single image, optionally repeated along either axis.
+pub struct ImageLayer {
+   /// The image. This is `None` if the layer doesn't have an image, or the image couldn't be
+   /// loaded.
+   #[cfg(feature = "game")]
+   texture: Option<Texture>,
+   /// The scale the image is drawn at, such that one pixel of the image is as large as one pixel
+   /// of the map's tiles.
+   scale: Vec2<f32>,
+   repeat_x: bool,
+   repeat_y: bool,
+}
+
+/// The contents of a layer.
+pub enum LayerKind {
+   Tile(TileLayer),
+   Image(ImageLayer),
    Object,
 }
 
+/// A layer.
+pub struct Layer {
+   pub name: String,
+   /// Whether the layer is drawn. Invisible layers still have colliders, so they can be used for
+   /// invisible walls.
+   pub visible: bool,
+   pub opacity: f32,
+   /// The offset the layer is drawn at, in world units. Object layers have their offsets applied
+   /// to their objects when they're loaded.
+   pub offset: Vec2<f32>,
+   /// The speed the layer scrolls at relative to the camera, see [`tiled::Layer::parallax_x`].
+   pub parallax: Vec2<f32>,
+   pub properties: tiled::Properties,
+   pub kind: LayerKind,
+}
+
+/// How the geometry of tile layers is batched into meshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshBatching {
+   /// Every chunk is drawn with its own mesh. This is much slower than batching into regions,
+   /// but makes it easy to see which chunks get regenerated.
+   Chunks,
+   /// Chunks are merged into larger regions, each of which is drawn with a single mesh.
+   Regions,
+}
+
 /// An in-game map.
 pub struct Map {
+   pub settings: MapSettings,
    pub tileset: Tileset,
    pub layers: Vec<Layer>,
+   pub mesh_batching: MeshBatching,
+   /// The files the map was loaded from: the map file itself, its external tilesets, and its
+   /// templates.
+   pub sources: Vec<PathBuf>,
 }
 
 impl Map {
-   /// Returns the map's tile size. Note that this is not the actual size things are rendered and
-   /// simulated at, but rather the size of tiles that should be used in the Tiled map.
-   pub fn tile_size() -> Vec2<f32> {
-      vector(16.0, 16.0)
+   /// The path to the Tiled source of the map the game loads.
+   pub const SOURCE: &'static str = "res/map/map.tmx";
+
+   /// Returns the path to the map's Tiled sources, which are loaded when the compiled map can't
+   /// be, so that changes made in the editor show up without compiling the map first.
+   pub fn source_path() -> PathBuf {
+      PathBuf::from(Self::SOURCE)
+   }
+
+   /// Returns the path to the compiled map generated by the asset compiler (`memphlus-assets`).
+   pub fn compiled_path() -> PathBuf {
+      asset_path("generated/map.bin")
+   }
+
+   /// Returns the map's tile size, in pixels. Note that this is not the actual size things are
+   /// rendered and simulated at, but rather the size of tiles used in the Tiled map.
+   pub fn tile_size(&self) -> Vec2<f32> {
+      self.settings.tile_size
+   }
+
+   /// Converts a position in world coordinates to the position of the tile it's in.
+   pub fn tile_position(position: Vec2<f32>) -> (i32, i32) {
+      (position.x.floor() as i32, position.y.floor() as i32)
+   }
+
+   /// Returns the tile at the given position in world coordinates, in the layer with the given
+   /// index. Returns `None` if the layer doesn't exist or isn't a tile layer.
+   pub fn tile_at(&self, layer: usize, position: Vec2<f32>) -> Option<Tile> {
+      match &self.layers.get(layer)?.kind {
+         LayerKind::Tile(layer) => Some(layer.tile_at(Self::tile_position(position))),
+         LayerKind::Image(_) | LayerKind::Object => None,
+      }
+   }
+
+   /// Replaces the tile at the given position in world coordinates, in the layer with the given
+   /// index. Returns the tile that was there previously.
+   pub fn set_tile(
+      &mut self,
+      physics: &mut Physics,
+      layer: usize,
+      position: Vec2<f32>,
+      tile: Tile,
+   ) -> anyhow::Result<Tile> {
+      if !self.tileset.contains(tile.id) {
+         anyhow::bail!("tile ID {} does not belong to any tileset", tile.id);
+      }
+      match self.layers.get_mut(layer).map(|layer| &mut layer.kind) {
+         Some(LayerKind::Tile(layer)) => {
+            Ok(layer.set_tile(Self::tile_position(position), tile, &self.tileset, physics))
+         }
+         Some(LayerKind::Image(_) | LayerKind::Object) => {
+            anyhow::bail!("layer {} is not a tile layer", layer)
+         }
+         None => anyhow::bail!("layer {} does not exist", layer),
+      }
    }
 
-   /// Loads a map from tileset and map JSON data.
-   pub fn load_into_world_from_json(
+   /// Loads a map from a Tiled map file. The tilesets and templates the map uses are loaded
+   /// relative to the map's directory. The entities spawned from the map's objects are registered
+   /// in an [`ObjectRegistry`] resource, replacing the registry of any previously loaded map.
+   #[cfg(feature = "game")]
+   pub fn load_into_world(
+      ctx: &mut Context,
+      resources: &mut Resources,
       world: &mut World,
       physics: &mut Physics,
-      tileset_json: &str,
-      map_json: &str,
+      path: &Path,
    ) -> anyhow::Result<Self> {
-      let tileset = tiled::Tileset::load_from_json(tileset_json)?;
-      let tileset = Tileset::try_from(tileset)?;
-      let map = tiled::Map::load_from_json(map_json)?;
+      let source = TiledSource::load(path)?;
+      let mut loader = Loader {
+         objects: ObjectRegistry::default(),
+         tile_size: source.settings.tile_size,
+         diagnostics: Vec::new(),
+      };
+      let layers = loader.load_layers(source.layers, ctx, world, physics, &source.tileset);
+      let map = Self {
+         layers,
+         settings: source.settings,
+         tileset: source.tileset,
+         mesh_batching: MeshBatching::Regions,
+         sources: source.sources,
+      };
+      Ok(loader.finish(map, resources, world))
+   }
+}
+
+/// A Tiled map, along with everything read from it that's needed before its layers can be loaded.
+struct TiledSource {
+   layers: Vec<tiled::Layer>,
+   settings: MapSettings,
+   tileset: Tileset,
+   /// The map file itself, its external tilesets, and its templates.
+   sources: Vec<PathBuf>,
+}
+
+impl TiledSource {
+   /// Loads a Tiled map file, and reads its settings and tilesets.
+   fn load(path: &Path) -> anyhow::Result<Self> {
+      let map = tiled::Map::load(path)?;
+      let settings = MapSettings::from_map(&map)
+         .with_context(|| format!("invalid map properties in {:?}", path))?;
+      let sources = std::iter::once(path.to_owned())
+         .chain(map.tilesets.iter().filter_map(|tileset| tileset.source.clone()))
+         .chain(map.templates.iter().cloned())
+         .collect();
+      let tileset = Tileset::try_from(map.tilesets)
+         .with_context(|| format!("cannot load tilesets of {:?}", path))?;
       Ok(Self {
-         layers: Loader {
-            objects: HashMap::new(),
-         }
-         .load_layers(map.layers, world, physics, &tileset),
+         layers: map.layers,
+         settings,
          tileset,
+         sources,
       })
    }
 }
 
 /// Map loading state.
 pub(super) struct Loader {
-   objects: HashMap<ObjectId, Entity>,
+   objects: ObjectRegistry,
+   /// The size of the map's tiles, in pixels. Positions in the Tiled map are divided by this to
+   /// convert them to world units.
+   tile_size: Vec2<f32>,
+   /// Problems found while loading, which didn't stop the map from being loaded.
+   diagnostics: Vec<Diagnostic>,
 }
 
 impl Loader {
    /// Returns the entity ID of the object with the given ID.
    pub(super) fn entity(&mut self, world: &mut World, object_id: ObjectId) -> Entity {
-      if !self.objects.contains_key(&object_id) {
-         self.objects.insert(object_id, world.reserve_entity());
+      self.objects.reserve(world, object_id)
+   }
+
+   /// Finishes loading a map whose layers have been loaded: reports the problems found along the
+   /// way, spawns the map's playable area, and stores the object registry in the resources.
+   fn finish(mut self, map: Map, resources: &mut Resources, world: &mut World) -> Map {
+      for diagnostic in &self.diagnostics {
+         diagnostic.log();
       }
-      *self.objects.get(&object_id).unwrap()
+      map.spawn_fallback_bounds(world);
+      self.objects.discard_unspawned(world);
+      resources.insert(self.objects);
+      map
    }
 
    /// Loads all layers from the given Vec.
+   #[cfg(feature = "game")]
    fn load_layers(
       &mut self,
       layers: Vec<tiled::Layer>,
+      ctx: &mut Context,
       world: &mut World,
       physics: &mut Physics,
       tileset: &Tileset,
    ) -> Vec<Layer> {
-      layers.into_iter().map(|layer| self.load_layer(layer, world, physics, tileset)).collect()
+      layers.into_iter().map(|layer| self.load_layer(layer, ctx, world, physics, tileset)).collect()
    }
 
    /// Loads a single tiled layer into an actual layer.
+   #[cfg(feature = "game")]
    fn load_layer(
       &mut self,
       data: tiled::Layer,
+      ctx: &mut Context,
       world: &mut World,
       physics: &mut Physics,
       tileset: &Tileset,
    ) -> Layer {
-      match data.kind {
-         tiled::LayerKind::Tile { chunks } => Self::create_tile_layer(chunks, tileset, physics),
-         tiled::LayerKind::Object { objects } => self.create_object_layer(objects, world, physics),
+      let kind = match data.kind {
+         tiled::LayerKind::Tile(tiles) => Self::create_tile_layer(tiles, tileset, physics),
+         tiled::LayerKind::Image(image) => self.create_image_layer(ctx, image),
+         tiled::LayerKind::Group { .. } => {
+            unreachable!("group layers are flattened when the map is loaded")
+         }
+         tiled::LayerKind::Object { mut objects } => {
+            for object in &mut objects {
+               object.x += data.offset_x;
+               object.y += data.offset_y;
+            }
+            self.create_object_layer(&data.name, objects, world, physics)
+         }
+      };
+      Layer {
+         name: data.name,
+         visible: data.visible,
+         opacity: data.opacity,
+         offset: vector(data.offset_x, data.offset_y) / self.tile_size,
+         parallax: vector(data.parallax_x, data.parallax_y),
+         properties: data.properties,
+         kind,
       }
    }
+
+   /// Creates an image layer, loading its image. Images are drawn with nearest-neighbour
+   /// filtering, as their colors are remapped to the palette after drawing.
+   #[cfg(feature = "game")]
+   fn create_image_layer(&self, ctx: &mut Context, data: tiled::ImageLayer) -> LayerKind {
+      let texture = if data.image.as_os_str().is_empty() {
+         None
+      } else {
+         match Texture::new(ctx, &data.image) {
+            Ok(mut texture) => {
+               texture.set_filter_mode(ctx, FilterMode::Nearest);
+               Some(texture)
+            }
+            Err(error) => {
+               error!("cannot load image {:?}: {}", data.image, error);
+               None
+            }
+         }
+      };
+      LayerKind::Image(ImageLayer {
+         texture,
+         scale: vector(1.0, 1.0) / self.tile_size,
+         repeat_x: data.repeat_x,
+         repeat_y: data.repeat_y,
+      })
+   }
 }
@@ -0,0 +1,68 @@
+//! Looking up the entities spawned from the map's objects.
+
+use std::collections::{HashMap, HashSet};
+
+use hecs::{Entity, World};
+
+use crate::tiled::ObjectId;
+
+/// A resource mapping the IDs and names of the map's objects to the entities they were spawned
+/// as. Unlike IDs, names don't have to be unique, so looking up an object by its name returns the
+/// first object with that name.
+#[derive(Default)]
+pub struct ObjectRegistry {
+   ids: HashMap<ObjectId, Entity>,
+   names: HashMap<String, Vec<Entity>>,
+   /// The IDs of objects whose entities have been spawned. This is only used while the map is
+   /// loading, to tell apart entities that were reserved but never spawned.
+   spawned: HashSet<ObjectId>,
+}
+
+impl ObjectRegistry {
+   /// Returns the entity of the object with the given ID. If the object hasn't been spawned yet,
+   /// an entity is reserved for it, so that objects can refer to objects spawned after them.
+   pub(super) fn reserve(&mut self, world: &World, id: ObjectId) -> Entity {
+      *self.ids.entry(id).or_insert_with(|| world.reserve_entity())
+   }
+
+   /// Marks the entity of the object with the given ID as spawned.
+   pub(super) fn mark_spawned(&mut self, id: ObjectId) {
+      self.spawned.insert(id);
+   }
+
+   /// Forgets the objects whose entities were reserved but never spawned: objects that failed to
+   /// spawn, and IDs referred to by other objects that don't exist in the map. Their reserved
+   /// entities are despawned from the world, so they don't linger as empty entities.
+   pub(super) fn discard_unspawned(&mut self, world: &mut World) {
+      let spawned = std::mem::take(&mut self.spawned);
+      self.ids.retain(|id, &mut entity| {
+         if spawned.contains(id) {
+            true
+         } else {
+            let _ = world.despawn(entity);
+            false
+         }
+      });
+   }
+
+   /// Registers the name of an object's entity.
+   pub(super) fn insert_name(&mut self, name: String, entity: Entity) {
+      self.names.entry(name).or_default().push(entity);
+   }
+
+   /// Returns the entity of the object with the given ID. Objects that failed to spawn don't have
+   /// entities.
+   pub fn by_id(&self, id: ObjectId) -> Option<Entity> {
+      self.ids.get(&id).copied()
+   }
+
+   /// Returns the entity of the first object with the given name.
+   pub fn by_name(&self, name: &str) -> Option<Entity> {
+      self.all_by_name(name).first().copied()
+   }
+
+   /// Returns the entities of all objects with the given name, in the order they were spawned.
+   pub fn all_by_name(&self, name: &str) -> &[Entity] {
+      self.names.get(name).map(|entities| &entities[..]).unwrap_or(&[])
+   }
+}
@@ -1,100 +1,175 @@
 //! Rendering of the map.
 
-use std::collections::HashMap;
+use std::ops::Range;
 
 use tetra::graphics::mesh::Mesh;
-use tetra::graphics::DrawParams;
+use tetra::graphics::{self, Color, DrawParams};
 use tetra::Context;
+use vek::Vec2;
 
-use crate::common::vector;
+use crate::common::{rect, vector, Rect};
 use crate::meshes::MeshBuilder;
-use crate::transform::{self, TransformStack};
+use crate::tiled::TileFlip;
 
-use super::tiles::TileKind;
-use super::{Chunk, Layer, Map, TileMeshes, Tileset};
+use super::tiles::{Corner, Sides, TileKind};
+use super::{
+   Chunk, ImageLayer, Layer, LayerKind, Map, MeshBatching, TileLayer, TileMeshes, Tileset,
+};
 
 impl Map {
-   /// Draws the map to the screen.
-   pub fn draw(&mut self, ctx: &mut Context, tstack: &mut TransformStack) -> anyhow::Result<()> {
-      for layer in &mut self.layers {
-         layer.draw(&self.tileset, ctx, tstack)?;
+   /// Draws the layers that are below the map's entities, that is, all layers up to and
+   /// including the last object layer. Only the parts of the map that intersect with `viewport`
+   /// (the world-space rectangle visible on the screen) are drawn.
+   pub fn draw_background(&mut self, ctx: &mut Context, viewport: Rect) -> anyhow::Result<()> {
+      let split = self.foreground_start();
+      self.draw_layers(ctx, viewport, 0..split)
+   }
+
+   /// Draws the layers that are above the map's entities, that is, all layers after the last
+   /// object layer.
+   pub fn draw_foreground(&mut self, ctx: &mut Context, viewport: Rect) -> anyhow::Result<()> {
+      let split = self.foreground_start();
+      self.draw_layers(ctx, viewport, split..self.layers.len())
+   }
+
+   /// Returns the index of the first layer drawn above entities.
+   fn foreground_start(&self) -> usize {
+      self
+         .layers
+         .iter()
+         .rposition(|layer| matches!(layer.kind, LayerKind::Object))
+         .map_or(self.layers.len(), |index| index + 1)
+   }
+
+   fn draw_layers(
+      &mut self,
+      ctx: &mut Context,
+      viewport: Rect,
+      range: Range<usize>,
+   ) -> anyhow::Result<()> {
+      // The blue channel is left alone, as it holds the palette selected by zones.
+      graphics::set_color_mask(ctx, true, true, false, true);
+      for layer in &mut self.layers[range] {
+         layer.draw(&self.tileset, ctx, viewport, self.mesh_batching)?;
       }
+      graphics::set_color_mask(ctx, true, true, true, true);
       Ok(())
    }
 }
 
 impl Chunk {
+   /// Adds the chunk's geometry to the mesh builder, with the chunk's top-left corner placed at
+   /// `origin`. Returns whether any vertices were added.
+   fn build_geometry(&self, tileset: &Tileset, mesh: &mut MeshBuilder, origin: Vec2<f32>) -> bool {
+      use super::tiles::TileKind::*;
+
+      let mut has_any_vertices = false;
+      for y in 0..Chunk::SIZE {
+         for x in 0..Chunk::SIZE {
+            let tile = self[(x, y)];
+            let tile_position = origin + vector(x as f32, y as f32);
+            let center = tile_position + vector(0.5, 0.5);
+            let kind = tileset.kind(tile.id);
+            let flip = tile.flip;
+            let mut block_has_vertices = true;
+            match kind {
+               | SolidTopLeft | SolidTop | SolidTopRight | SolidRight | SolidBottomRight
+               | SolidBottom | SolidBottomLeft | SolidLeft | SolidVTop | SolidVMiddle
+               | SolidVBottom | SolidHLeft | SolidHCenter | SolidHRight | SolidTile => {
+                  TileMeshes::build_sides(mesh, center, Self::sides(kind, flip))
+               }
+               | SolidTopFadeLeft | SolidBottomFadeLeft | SolidLeftFadeBottom
+               | SolidRightFadeBottom | SolidTopFadeRight | SolidBottomFadeRight
+               | SolidLeftFadeTop | SolidRightFadeTop => TileMeshes::build_fading_side(
+                  mesh,
+                  center,
+                  kind.side().unwrap().flipped(flip),
+                  Self::fade_opacities(kind, flip),
+               ),
+               | SolidCornerTopLeft
+               | SolidCornerTopRight
+               | SolidCornerBottomRight
+               | SolidCornerBottomLeft => {
+                  TileMeshes::build_corner(mesh, center, kind.corner().unwrap().flipped(flip))
+               }
+               | SolidPipeTopLeft | SolidPipeTopRight | SolidPipeBottomRight
+               | SolidPipeBottomLeft => {
+                  TileMeshes::build_sides(mesh, center, Self::sides(kind, flip));
+                  TileMeshes::build_corner(mesh, center, kind.corner().unwrap().flipped(flip))
+               }
+               Solid => {
+                  let outline = self.outline((x, y));
+                  TileMeshes::build_sides(mesh, center, outline.sides);
+                  for corner in Corner::ALL {
+                     if outline.corners.contains(corner.into()) {
+                        TileMeshes::build_corner(mesh, center, corner);
+                     }
+                  }
+               }
+               Barrier => TileMeshes::build_barrier(mesh, center),
+               SpikesUp | SpikesRight | SpikesDown | SpikesLeft => TileMeshes::build_spikes(
+                  mesh,
+                  center,
+                  kind.spike_direction().unwrap().flipped(flip),
+               ),
+               | SlopeUp
+               | SlopeDown
+               | SlopeHalfUpLeft
+               | SlopeHalfUpRight
+               | SlopeHalfDownLeft
+               | SlopeHalfDownRight
+               | SlopeDoubleUpBottom
+               | SlopeDoubleUpTop
+               | SlopeDoubleDownBottom
+               | SlopeDoubleDownTop => {
+                  TileMeshes::build_slope(mesh, center, kind.slope_line().unwrap(), flip)
+               }
+               _ => block_has_vertices = false,
+            }
+            has_any_vertices |= block_has_vertices;
+         }
+      }
+      has_any_vertices
+   }
+
    /// Returns the cached mesh or regenerates the mesh for a chunk.
    fn get_or_generate_mesh(
       &mut self,
       tileset: &Tileset,
       ctx: &mut Context,
    ) -> anyhow::Result<Option<&Mesh>> {
-      use super::tiles::TileKind::*;
-
       if self.mesh.is_none() {
          let mut mesh = MeshBuilder::new();
-         let mut has_any_vertices = false;
-         for y in 0..Chunk::SIZE {
-            for x in 0..Chunk::SIZE {
-               let tile_id = self[(x, y)];
-               let tile_position = vector(x as f32, y as f32);
-               let center = tile_position + vector(0.5, 0.5);
-               let kind = tileset.kind(tile_id);
-               let mut block_has_vertices = true;
-               match kind {
-                  | SolidTopLeft | SolidTop | SolidTopRight | SolidRight | SolidBottomRight
-                  | SolidBottom | SolidBottomLeft | SolidLeft | SolidVTop | SolidVMiddle
-                  | SolidVBottom | SolidHLeft | SolidHCenter | SolidHRight | SolidTile => {
-                     TileMeshes::build_sides(&mut mesh, center, kind.try_into().unwrap())
-                  }
-                  | SolidTopFadeLeft | SolidBottomFadeLeft | SolidLeftFadeBottom
-                  | SolidRightFadeBottom | SolidTopFadeRight | SolidBottomFadeRight
-                  | SolidLeftFadeTop | SolidRightFadeTop => TileMeshes::build_fading_side(
-                     &mut mesh,
-                     center,
-                     kind.side().unwrap(),
-                     Self::fade_opacities(kind),
-                  ),
-                  | SolidCornerTopLeft
-                  | SolidCornerTopRight
-                  | SolidCornerBottomRight
-                  | SolidCornerBottomLeft => {
-                     TileMeshes::build_corner(&mut mesh, center, kind.corner().unwrap())
-                  }
-                  | SolidPipeTopLeft | SolidPipeTopRight | SolidPipeBottomRight
-                  | SolidPipeBottomLeft => {
-                     TileMeshes::build_sides(&mut mesh, center, kind.try_into().unwrap());
-                     TileMeshes::build_corner(&mut mesh, center, kind.corner().unwrap())
-                  }
-                  SpikesUp | SpikesRight | SpikesDown | SpikesLeft => {
-                     TileMeshes::build_spikes(&mut mesh, center, kind.spike_direction().unwrap())
-                  }
-                  _ => block_has_vertices = false,
-               }
-               has_any_vertices = has_any_vertices | block_has_vertices;
-            }
-         }
-         if !has_any_vertices {
+         if !self.build_geometry(tileset, &mut mesh, vector(0.0, 0.0)) {
             return Ok(None);
          }
-
          self.mesh = Some(mesh.build(ctx)?)
       }
       Ok(self.mesh.as_ref())
    }
 
-   fn draw(&mut self, tileset: &Tileset, ctx: &mut Context) -> anyhow::Result<()> {
+   fn draw(
+      &mut self,
+      tileset: &Tileset,
+      ctx: &mut Context,
+      params: DrawParams,
+   ) -> anyhow::Result<()> {
       if let Some(mesh) = self.get_or_generate_mesh(tileset, ctx)? {
-         mesh.draw(ctx, DrawParams::new())
+         mesh.draw(ctx, params)
       }
 
       Ok(())
    }
 
-   fn fade_opacities(kind: TileKind) -> (f32, f32) {
+   /// Returns the outlined sides of a solid tile, after flipping it.
+   fn sides(kind: TileKind, flip: TileFlip) -> Sides {
+      Sides::try_from(kind).unwrap().flipped(flip)
+   }
+
+   /// Returns the opacities at the start and end of a fading side, after flipping its tile.
+   fn fade_opacities(kind: TileKind, flip: TileFlip) -> (f32, f32) {
       use super::tiles::TileKind::*;
-      match kind {
+      let (start, end) = match kind {
          SolidTopFadeLeft | SolidBottomFadeLeft | SolidLeftFadeTop | SolidRightFadeTop => {
             (0.0, 1.0)
          }
@@ -102,6 +177,11 @@ impl Chunk {
             (1.0, 0.0)
          }
          _ => unreachable!(),
+      };
+      if kind.side().unwrap().flip_reverses_axis(flip) {
+         (end, start)
+      } else {
+         (start, end)
       }
    }
 }
@@ -111,28 +191,154 @@ impl Layer {
       &mut self,
       tileset: &Tileset,
       ctx: &mut Context,
-      tstack: &mut TransformStack,
+      viewport: Rect,
+      batching: MeshBatching,
    ) -> anyhow::Result<()> {
-      match self {
-         Layer::Tile { chunks } => Self::draw_chunks(chunks, tileset, ctx, tstack),
-         Layer::Object => Ok(()),
+      if !self.visible {
+         return Ok(());
+      }
+
+      // Parallax is relative to the center of the screen, such that a layer lines up with the
+      // rest of the map when the camera is centered on the map's origin.
+      let translation = self.offset + viewport.center() * (vector(1.0, 1.0) - self.parallax);
+      let params =
+         DrawParams::new().position(translation).color(Color::WHITE.with_alpha(self.opacity));
+      let viewport = rect(
+         viewport.top_left() - translation,
+         vector(viewport.width, viewport.height),
+      );
+      match &mut self.kind {
+         LayerKind::Tile(layer) => match batching {
+            MeshBatching::Chunks => layer.draw_chunks(tileset, ctx, viewport, params),
+            MeshBatching::Regions => layer.draw_regions(tileset, ctx, viewport, params),
+         },
+         LayerKind::Image(layer) => {
+            layer.draw(ctx, viewport, params);
+            Ok(())
+         }
+         LayerKind::Object => Ok(()),
+      }
+   }
+}
+
+impl ImageLayer {
+   /// Draws the image, repeating it such that it covers the viewport along the axes it's
+   /// repeated on. The image's top-left corner is placed at the position in `params`.
+   fn draw(&self, ctx: &mut Context, viewport: Rect, params: DrawParams) {
+      let texture = match &self.texture {
+         Some(texture) => texture,
+         None => return,
+      };
+      let scale = self.scale;
+      let size = vector(texture.width() as f32, texture.height() as f32) * scale;
+      let repetitions = |repeat: bool, start: f32, end: f32, size: f32| {
+         if repeat {
+            (start / size).floor() as i32..(end / size).ceil() as i32
+         } else {
+            0..1
+         }
+      };
+      for y in repetitions(self.repeat_y, viewport.top(), viewport.bottom(), size.y) {
+         for x in repetitions(self.repeat_x, viewport.left(), viewport.right(), size.x) {
+            let position = vector(x as f32, y as f32) * size;
+            if viewport.intersects(&rect(position, size)) {
+               let params = params.clone().position(params.position + position).scale(scale);
+               texture.draw(ctx, params);
+            }
+         }
+      }
+   }
+}
+
+impl TileLayer {
+   /// The size of a region, in chunks.
+   const REGION_SIZE_BITS: u32 = 2;
+   const REGION_SIZE: usize = 1 << Self::REGION_SIZE_BITS;
+
+   /// Discards the cached meshes of the chunk at the given position and the region it belongs
+   /// to, such that they're regenerated the next time they're drawn.
+   pub(super) fn invalidate_mesh(&mut self, chunk_position: (i32, i32)) {
+      if let Some(chunk) = self.chunks.get_mut(&chunk_position) {
+         chunk.invalidate_mesh();
       }
+      let (x, y) = chunk_position;
+      self.regions.remove(&(x >> Self::REGION_SIZE_BITS, y >> Self::REGION_SIZE_BITS));
    }
 
+   /// Draws all chunks that intersect with the viewport, each with its own mesh. The chunks are
+   /// positioned relative to the position in `params`.
    fn draw_chunks(
-      chunks: &mut HashMap<(i32, i32), Chunk>,
+      &mut self,
       tileset: &Tileset,
       ctx: &mut Context,
-      tstack: &mut TransformStack,
+      viewport: Rect,
+      params: DrawParams,
    ) -> anyhow::Result<()> {
-      for (&(x, y), chunk) in chunks {
-         tstack.save(ctx);
-         let offset = vector(x as f32, y as f32) * vector(Chunk::SIZE as f32, Chunk::SIZE as f32);
-         transform::translate(ctx, offset);
+      let chunk_size = vector(Chunk::SIZE as f32, Chunk::SIZE as f32);
+      for (&(x, y), chunk) in &mut self.chunks {
+         let position = vector(x as f32, y as f32) * chunk_size;
+         if viewport.intersects(&rect(position, chunk_size)) {
+            let params = params.clone().position(params.position + position);
+            chunk.draw(tileset, ctx, params)?;
+         }
+      }
+      Ok(())
+   }
 
-         chunk.draw(tileset, ctx)?;
-         tstack.restore(ctx);
+   /// Draws all regions that intersect with the viewport. Regions whose meshes aren't cached
+   /// are regenerated from their chunks.
+   fn draw_regions(
+      &mut self,
+      tileset: &Tileset,
+      ctx: &mut Context,
+      viewport: Rect,
+      params: DrawParams,
+   ) -> anyhow::Result<()> {
+      let region_size = (Self::REGION_SIZE * Chunk::SIZE) as f32;
+      let top_left = (viewport.top_left() / region_size).floor();
+      let bottom_right = (viewport.bottom_right() / region_size).ceil();
+      for y in top_left.y as i32..bottom_right.y as i32 {
+         for x in top_left.x as i32..bottom_right.x as i32 {
+            if let Some(mesh) = self.get_or_generate_region_mesh((x, y), tileset, ctx)? {
+               mesh.draw(ctx, params.clone());
+            }
+         }
       }
       Ok(())
    }
+
+   /// Returns the cached mesh or regenerates the mesh for a region. The region's geometry is
+   /// baked in world space, so the mesh does not need to be transformed when drawn.
+   fn get_or_generate_region_mesh(
+      &mut self,
+      region_position: (i32, i32),
+      tileset: &Tileset,
+      ctx: &mut Context,
+   ) -> anyhow::Result<Option<&Mesh>> {
+      if !self.regions.contains_key(&region_position) {
+         let mut mesh = MeshBuilder::new();
+         let mut has_any_vertices = false;
+         let (region_x, region_y) = region_position;
+         for y in 0..Self::REGION_SIZE as i32 {
+            for x in 0..Self::REGION_SIZE as i32 {
+               let chunk_position = (
+                  (region_x << Self::REGION_SIZE_BITS) + x,
+                  (region_y << Self::REGION_SIZE_BITS) + y,
+               );
+               if let Some(chunk) = self.chunks.get(&chunk_position) {
+                  let origin =
+                     vector(chunk_position.0 as f32, chunk_position.1 as f32) * Chunk::SIZE as f32;
+                  has_any_vertices |= chunk.build_geometry(tileset, &mut mesh, origin);
+               }
+            }
+         }
+         let mesh = if has_any_vertices {
+            Some(mesh.build(ctx)?)
+         } else {
+            None
+         };
+         self.regions.insert(region_position, mesh);
+      }
+      Ok(self.regions[&region_position].as_ref())
+   }
 }
@@ -0,0 +1,92 @@
+//! Map-wide settings.
+
+use vek::Vec2;
+
+use crate::common::vector;
+use crate::entities::zones::Zones;
+use crate::tiled;
+
+/// Settings that apply to the whole map. These are read from the map's custom properties:
+///
+/// - `gravity_x`, `gravity_y` (float) - the gravity, in tiles per second squared
+/// - `palette` (int) - the index of the palette used outside of zones
+/// - `name` (string) - the name of the map, shown in the window's title
+/// - `music` (string) - the music track, as a path relative to the assets directory
+#[derive(Debug, Clone)]
+pub struct MapSettings {
+   pub gravity: Vec2<f32>,
+   pub palette: usize,
+   pub name: Option<String>,
+   pub music: Option<String>,
+   /// The size of the map's tiles, in pixels. This is taken from the map's tile width and height
+   /// rather than a custom property.
+   pub tile_size: Vec2<f32>,
+}
+
+impl MapSettings {
+   /// The gravity used by maps that don't set their own.
+   pub const DEFAULT_GRAVITY: Vec2<f32> = Vec2::new(0.0, 40.0);
+
+   /// Reads the settings from a map.
+   pub fn from_map(map: &tiled::Map) -> anyhow::Result<Self> {
+      let properties = &map.properties;
+      let float = |name: &str, default: f32| match properties.get(name) {
+         Some(value) => value
+            .as_float()
+            .ok_or_else(|| anyhow::anyhow!("the '{}' map property must be a float", name)),
+         None => Ok(default),
+      };
+      let string = |name: &str| match properties.get(name) {
+         Some(value) => value
+            .as_str()
+            .map(|value| Some(value.to_owned()))
+            .ok_or_else(|| anyhow::anyhow!("the '{}' map property must be a string", name)),
+         None => Ok(None),
+      };
+
+      let palette = match properties.get("palette") {
+         Some(value) => value
+            .as_int()
+            .ok_or_else(|| anyhow::anyhow!("the 'palette' map property must be an int"))?,
+         None => 0,
+      };
+      if !(0..Zones::MAX as i32).contains(&palette) {
+         anyhow::bail!(
+            "palette {} is out of range (there are only {} palettes)",
+            palette,
+            Zones::MAX
+         );
+      }
+
+      for tiled::MapTileset {
+         first_gid, tileset, ..
+      } in &map.tilesets
+      {
+         if (tileset.tile_width, tileset.tile_height) != (map.tile_width, map.tile_height) {
+            anyhow::bail!(
+               "the tileset with firstgid {} has {}x{} tiles, but the map's tiles are {}x{}",
+               first_gid,
+               tileset.tile_width,
+               tileset.tile_height,
+               map.tile_width,
+               map.tile_height
+            );
+         }
+      }
+      if map.tile_width == 0 || map.tile_height == 0 {
+         anyhow::bail!("the map's tiles must not be empty");
+      }
+      let tile_size = vector(map.tile_width as f32, map.tile_height as f32);
+
+      Ok(Self {
+         gravity: vector(
+            float("gravity_x", Self::DEFAULT_GRAVITY.x)?,
+            float("gravity_y", Self::DEFAULT_GRAVITY.y)?,
+         ),
+         palette: palette as usize,
+         name: string("name")?,
+         music: string("music")?,
+         tile_size,
+      })
+   }
+}
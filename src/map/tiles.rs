@@ -4,16 +4,17 @@ use std::str::FromStr;
 
 /// A map tile.
 use bitflags::bitflags;
-use rapier2d::prelude::{ColliderBuilder, InteractionGroups};
+use log::warn;
+use rapier2d::prelude::{ColliderBuilder, ColliderHandle, InteractionGroups, Point};
 use serde::de::IntoDeserializer;
 use serde::{Deserialize, Serialize};
 use vek::Vec2;
 
-use crate::common::{vector, Axis, ToNalgebraVector2};
+use crate::common::{rect, vector, Axis, Rect, ToNalgebraVector2};
 use crate::physics::{CollisionGroups, Physics};
-use crate::tiled;
+use crate::tiled::{self, TileFlip};
 
-use super::{Chunk, Layer, Loader, Tileset};
+use super::{Chunk, LayerKind, Loader, Tile, TileLayer, Tileset};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 #[repr(u8)]
@@ -54,6 +55,8 @@ pub enum TileKind {
    SolidLeftFadeBottom,
    SolidRightFadeTop,
    SolidRightFadeBottom,
+   /// An auto-tiled solid block, whose outline is derived from the tiles surrounding it.
+   Solid,
    Barrier,
 
    // Slopes
@@ -77,6 +80,46 @@ pub enum TileKind {
 }
 
 impl TileKind {
+   /// Returns whether the tile is part of a solid block, and should thus collide with the player.
+   pub fn is_solid(&self) -> bool {
+      use TileKind::*;
+      matches!(
+         self,
+         SolidTopLeft
+            | SolidTop
+            | SolidTopRight
+            | SolidRight
+            | SolidBottomRight
+            | SolidBottom
+            | SolidBottomLeft
+            | SolidLeft
+            | SolidVTop
+            | SolidVMiddle
+            | SolidVBottom
+            | SolidHLeft
+            | SolidHCenter
+            | SolidHRight
+            | SolidTile
+            | SolidCornerTopLeft
+            | SolidCornerTopRight
+            | SolidCornerBottomRight
+            | SolidCornerBottomLeft
+            | SolidPipeTopLeft
+            | SolidPipeTopRight
+            | SolidPipeBottomRight
+            | SolidPipeBottomLeft
+            | SolidTopFadeLeft
+            | SolidTopFadeRight
+            | SolidBottomFadeLeft
+            | SolidBottomFadeRight
+            | SolidLeftFadeTop
+            | SolidLeftFadeBottom
+            | SolidRightFadeTop
+            | SolidRightFadeBottom
+            | Solid
+      )
+   }
+
    /// Returns the sole side which this tile represents.
    pub fn side(&self) -> Option<Side> {
       match self {
@@ -103,6 +146,24 @@ impl TileKind {
       }
    }
 
+   /// Returns the line along which the surface of a slope runs.
+   pub fn slope_line(&self) -> Option<SlopeLine> {
+      let (slope, intercept) = match self {
+         Self::SlopeUp => (1.0, 0.0),
+         Self::SlopeDown => (-1.0, 1.0),
+         Self::SlopeHalfUpLeft => (0.5, 0.0),
+         Self::SlopeHalfUpRight => (0.5, 0.5),
+         Self::SlopeHalfDownLeft => (-0.5, 1.0),
+         Self::SlopeHalfDownRight => (-0.5, 0.5),
+         Self::SlopeDoubleUpBottom => (2.0, 0.0),
+         Self::SlopeDoubleUpTop => (2.0, -1.0),
+         Self::SlopeDoubleDownBottom => (-2.0, 2.0),
+         Self::SlopeDoubleDownTop => (-2.0, 1.0),
+         _ => return None,
+      };
+      Some(SlopeLine { slope, intercept })
+   }
+
    pub fn corner(&self) -> Option<Corner> {
       match self {
          Self::SolidCornerTopLeft | Self::SolidPipeTopLeft => Some(Corner::TopLeft),
@@ -139,6 +200,46 @@ impl Side {
          Side::Left | Side::Right => Axis::Y,
       }
    }
+
+   /// Returns the direction the side is facing.
+   fn normal(&self) -> Vec2<f32> {
+      match self {
+         Side::Top => vector(0.0, -1.0),
+         Side::Bottom => vector(0.0, 1.0),
+         Side::Left => vector(-1.0, 0.0),
+         Side::Right => vector(1.0, 0.0),
+      }
+   }
+
+   /// Returns the side facing in the given axis-aligned direction.
+   fn from_normal(normal: Vec2<f32>) -> Self {
+      if normal.x < 0.0 {
+         Side::Left
+      } else if normal.x > 0.0 {
+         Side::Right
+      } else if normal.y < 0.0 {
+         Side::Top
+      } else {
+         Side::Bottom
+      }
+   }
+
+   /// Returns where the side ends up after flipping its tile.
+   pub fn flipped(self, flip: TileFlip) -> Self {
+      Self::from_normal(flip.apply(self.normal()))
+   }
+
+   /// Returns whether flipping the side's tile reverses the direction of its axis, eg. for
+   /// [`Side::Top`], whether what was on its left is now on its right (or bottom, after a
+   /// diagonal flip).
+   pub fn flip_reverses_axis(self, flip: TileFlip) -> bool {
+      let direction = match self.axis() {
+         Axis::X => vector(1.0, 0.0),
+         Axis::Y => vector(0.0, 1.0),
+      };
+      let flipped = flip.apply(direction);
+      flipped.x + flipped.y < 0.0
+   }
 }
 
 bitflags! {
@@ -151,6 +252,35 @@ bitflags! {
    }
 }
 
+impl Sides {
+   /// Returns the sides after flipping their tile.
+   pub fn flipped(self, flip: TileFlip) -> Self {
+      let mut flipped = Sides::empty();
+      for (side, flag) in [
+         (Side::Top, Sides::TOP),
+         (Side::Bottom, Sides::BOTTOM),
+         (Side::Left, Sides::LEFT),
+         (Side::Right, Sides::RIGHT),
+      ] {
+         if self.contains(flag) {
+            flipped |= Sides::from(side.flipped(flip));
+         }
+      }
+      flipped
+   }
+}
+
+impl From<Side> for Sides {
+   fn from(side: Side) -> Self {
+      match side {
+         Side::Top => Sides::TOP,
+         Side::Bottom => Sides::BOTTOM,
+         Side::Left => Sides::LEFT,
+         Side::Right => Sides::RIGHT,
+      }
+   }
+}
+
 #[derive(Debug)]
 pub struct NotAxisAligned;
 
@@ -174,13 +304,72 @@ impl TryFrom<TileKind> for Sides {
          SolidHLeft => Ok(Sides::TOP | Sides::LEFT | Sides::BOTTOM),
          SolidHCenter => Ok(Sides::TOP | Sides::BOTTOM),
          SolidHRight => Ok(Sides::TOP | Sides::RIGHT | Sides::BOTTOM),
-         SolidTile => Ok(Sides::ALL),
-         Barrier => todo!(),
+         SolidTile | Barrier => Ok(Sides::ALL),
          _ => Err(NotAxisAligned),
       }
    }
 }
 
+/// The line along which the surface of a slope runs, `y = slope * x + intercept`.
+///
+/// The line is expressed in the same coordinate system as the equations next to the slopes in
+/// [`TileKind`]: the tile spans from `(0, 0)` to `(1, 1)`, and the Y axis points upwards. The area
+/// below the line is solid.
+#[derive(Debug, Clone, Copy)]
+pub struct SlopeLine {
+   pub slope: f32,
+   pub intercept: f32,
+}
+
+impl SlopeLine {
+   /// Returns the polygon covering the solid part of the tile that's at most `depth` units below
+   /// the line, measured perpendicular to it. Passing [`f32::INFINITY`] returns the entire solid
+   /// part of the tile.
+   ///
+   /// The polygon is convex, and its vertices are relative to the center of the tile, with the Y
+   /// axis pointing downwards like everywhere else in the game.
+   pub fn polygon(self, depth: f32, flip: TileFlip) -> Vec<Vec2<f32>> {
+      let Self { slope, intercept } = self;
+      let square = [
+         vector(0.0, 0.0),
+         vector(1.0, 0.0),
+         vector(1.0, 1.0),
+         vector(0.0, 1.0),
+      ];
+      let below = Self::clip(&square, |point| point.y - (slope * point.x + intercept));
+      let polygon = if depth.is_finite() {
+         // Convert the perpendicular depth into a vertical one.
+         let depth = depth * (1.0 + slope * slope).sqrt();
+         Self::clip(&below, |point| {
+            slope * point.x + intercept - depth - point.y
+         })
+      } else {
+         below
+      };
+      polygon.into_iter().map(|point| flip.apply(vector(point.x - 0.5, 0.5 - point.y))).collect()
+   }
+
+   /// Clips a convex polygon to the area where `distance` is not positive. `distance` must be a
+   /// linear function.
+   fn clip(polygon: &[Vec2<f32>], distance: impl Fn(Vec2<f32>) -> f32) -> Vec<Vec2<f32>> {
+      let mut clipped = Vec::new();
+      for (i, &current) in polygon.iter().enumerate() {
+         let next = polygon[(i + 1) % polygon.len()];
+         let (current_distance, next_distance) = (distance(current), distance(next));
+         if current_distance <= 0.0 {
+            clipped.push(current);
+         }
+         if (current_distance < 0.0 && next_distance > 0.0)
+            || (current_distance > 0.0 && next_distance < 0.0)
+         {
+            let t = current_distance / (current_distance - next_distance);
+            clipped.push(current + (next - current) * t);
+         }
+      }
+      clipped
+   }
+}
+
 /// Tile corners.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Corner {
@@ -190,55 +379,358 @@ pub enum Corner {
    BottomLeft,
 }
 
+impl Corner {
+   /// All corners, in clockwise order.
+   pub const ALL: [Corner; 4] = [
+      Corner::TopLeft,
+      Corner::TopRight,
+      Corner::BottomRight,
+      Corner::BottomLeft,
+   ];
+
+   /// Returns where the corner ends up after flipping its tile.
+   pub fn flipped(self, flip: TileFlip) -> Self {
+      let direction = match self {
+         Corner::TopLeft => vector(-1.0, -1.0),
+         Corner::TopRight => vector(1.0, -1.0),
+         Corner::BottomRight => vector(1.0, 1.0),
+         Corner::BottomLeft => vector(-1.0, 1.0),
+      };
+      match flip.apply(direction).map(|x| x > 0.0).into_tuple() {
+         (false, false) => Corner::TopLeft,
+         (true, false) => Corner::TopRight,
+         (true, true) => Corner::BottomRight,
+         (false, true) => Corner::BottomLeft,
+      }
+   }
+}
+
+bitflags! {
+   pub struct Corners: u8 {
+      const TOP_LEFT = 0b0001;
+      const TOP_RIGHT = 0b0010;
+      const BOTTOM_RIGHT = 0b0100;
+      const BOTTOM_LEFT = 0b1000;
+   }
+}
+
+impl From<Corner> for Corners {
+   fn from(corner: Corner) -> Self {
+      match corner {
+         Corner::TopLeft => Corners::TOP_LEFT,
+         Corner::TopRight => Corners::TOP_RIGHT,
+         Corner::BottomRight => Corners::BOTTOM_RIGHT,
+         Corner::BottomLeft => Corners::BOTTOM_LEFT,
+      }
+   }
+}
+
+/// The outline of an auto-tiled solid block ([`TileKind::Solid`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outline {
+   /// The sides that face tiles which aren't solid.
+   pub sides: Sides,
+   /// The inner corners, that is, corners whose two adjacent neighbours are solid, but whose
+   /// diagonal neighbour is not.
+   pub corners: Corners,
+}
+
+impl Outline {
+   /// An outline without any sides or corners.
+   pub const EMPTY: Self = Self {
+      sides: Sides::empty(),
+      corners: Corners::empty(),
+   };
+
+   /// Derives an outline from the eight neighbours of a tile. `is_solid` is called with the offset
+   /// of each neighbour, relative to the tile.
+   pub fn from_neighbours(is_solid: impl Fn(i32, i32) -> bool) -> Self {
+      let mut outline = Self::EMPTY;
+      for (side, (dx, dy)) in [
+         (Sides::TOP, (0, -1)),
+         (Sides::BOTTOM, (0, 1)),
+         (Sides::LEFT, (-1, 0)),
+         (Sides::RIGHT, (1, 0)),
+      ] {
+         if !is_solid(dx, dy) {
+            outline.sides |= side;
+         }
+      }
+      for (corner, (dx, dy)) in [
+         (Corners::TOP_LEFT, (-1, -1)),
+         (Corners::TOP_RIGHT, (1, -1)),
+         (Corners::BOTTOM_RIGHT, (1, 1)),
+         (Corners::BOTTOM_LEFT, (-1, 1)),
+      ] {
+         if is_solid(dx, 0) && is_solid(0, dy) && !is_solid(dx, dy) {
+            outline.corners |= corner;
+         }
+      }
+      outline
+   }
+}
+
 impl Loader {
-   /// Creates a new tile layer from a list of chunks.
+   /// Creates a new tile layer from Tiled's tile layer data.
    pub(super) fn create_tile_layer(
-      in_chunks: Vec<tiled::Chunk>,
+      data: tiled::TileLayer,
       tileset: &Tileset,
       physics: &mut Physics,
-   ) -> Layer {
+   ) -> LayerKind {
+      let chunks = Self::place_tiles(data, tileset);
+      LayerKind::Tile(TileLayer::from_chunks(chunks, tileset, None, physics))
+   }
+
+   /// Sorts the tiles of a Tiled tile layer into chunks. Chunks that end up empty are discarded.
+   pub(super) fn place_tiles(
+      data: tiled::TileLayer,
+      tileset: &Tileset,
+   ) -> HashMap<(i32, i32), Chunk> {
       let mut chunks = HashMap::new();
+      // Tiled's chunks don't necessarily have the same size as ours, so their tiles need to be
+      // sorted into our chunks one by one.
+      for (position, gid) in data.tiles() {
+         Self::place_tile(&mut chunks, position, gid, tileset);
+      }
+      chunks.retain(|_, chunk| !chunk.is_empty(tileset));
+      chunks
+   }
 
-      for chunk_data in in_chunks {
-         let chunk_position = (
-            chunk_data.x >> Chunk::SIZE_BITS,
-            chunk_data.y >> Chunk::SIZE_BITS,
+   /// Places a Tiled tile at the given position in the layer, creating the chunk it belongs to if
+   /// it doesn't exist yet.
+   fn place_tile(
+      chunks: &mut HashMap<(i32, i32), Chunk>,
+      (x, y): (i32, i32),
+      gid: tiled::Gid,
+      tileset: &Tileset,
+   ) {
+      let (chunk_position, position_in_chunk) = Chunk::split_position((x, y));
+      let chunk = chunks.entry(chunk_position).or_insert_with(|| Chunk::from_tile(Tile::EMPTY));
+      let id = gid.tile_id();
+      let tile = if !tileset.contains(id) {
+         warn!(
+            "tile at ({}, {}) has ID {}, which does not belong to any tileset",
+            x, y, id
          );
-         let mut chunk = Chunk::from_tile_id(0);
-         for y in 0..Chunk::SIZE {
-            for x in 0..Chunk::SIZE {
-               // Subtract 1 because empty tiles are represented as 0, but we already have an
-               // empty tile at ID 0 anyways.
-               let tile_id = chunk_data.data[x + y * Chunk::SIZE].saturating_sub(1);
-               chunk[(x, y)] = tile_id;
-               let kind = tileset.kind(tile_id);
-               let tile_top_left =
-                  vector(chunk_data.x as f32, chunk_data.y as f32) + vector(x as f32, y as f32);
-               Self::build_tile_extra(kind, physics, tile_top_left + vector(0.5, 0.5));
-            }
+         Tile::EMPTY
+      } else if !tileset.has_type(id) {
+         warn!(
+            "tile at ({}, {}) has ID {}, which does not have a type",
+            x, y, id
+         );
+         Tile::EMPTY
+      } else {
+         Tile {
+            id,
+            flip: gid.flip(),
          }
-         if !chunk.is_empty(tileset) {
-            chunks.insert(chunk_position, chunk);
+      };
+      chunk[position_in_chunk] = tile;
+   }
+}
+
+impl TileLayer {
+   /// Creates a tile layer from its chunks, deriving the outlines of auto-tiled blocks and
+   /// building the tiles' colliders.
+   ///
+   /// The merged colliders of solid tiles and barriers can be passed in `solids` if they're
+   /// already known, such as when the layer comes from a compiled map. Otherwise they're built
+   /// from the tiles.
+   pub(super) fn from_chunks(
+      chunks: HashMap<(i32, i32), Chunk>,
+      tileset: &Tileset,
+      solids: Option<&[SolidRect]>,
+      physics: &mut Physics,
+   ) -> Self {
+      let mut layer = Self::without_colliders(chunks);
+      let positions: Vec<_> = layer.tiles().map(|(position, _)| position).collect();
+      for &position in &positions {
+         layer.update_outline(position, tileset);
+         layer.build_tile_collider(position, tileset, physics);
+      }
+      match solids {
+         Some(solids) => layer.insert_solid_colliders(solids, physics),
+         None => layer.rebuild_solid_colliders(tileset, physics),
+      }
+      layer
+   }
+
+   /// Creates a tile layer from its chunks, without deriving outlines or building any colliders.
+   pub(super) fn without_colliders(chunks: HashMap<(i32, i32), Chunk>) -> Self {
+      Self {
+         chunks,
+         solids: Vec::new(),
+         tile_colliders: HashMap::new(),
+         #[cfg(feature = "game")]
+         regions: HashMap::new(),
+      }
+   }
+
+   /// Returns the tile at the given position. Tiles outside of the layer's chunks are empty.
+   pub fn tile_at(&self, position: (i32, i32)) -> Tile {
+      let (chunk_position, position_in_chunk) = Chunk::split_position(position);
+      self.chunks.get(&chunk_position).map_or(Tile::EMPTY, |chunk| chunk[position_in_chunk])
+   }
+
+   /// Replaces the tile at the given position, and returns the tile that was there previously.
+   ///
+   /// The meshes of all chunks affected by the change are regenerated the next time they're
+   /// drawn, and the tile's colliders are replaced. If the change affects solid tiles, the merged
+   /// colliders touching the tile are rebuilt.
+   pub fn set_tile(
+      &mut self,
+      position: (i32, i32),
+      tile: Tile,
+      tileset: &Tileset,
+      physics: &mut Physics,
+   ) -> Tile {
+      let previous = self.tile_at(position);
+      if previous == tile {
+         return previous;
+      }
+      // Positions outside of the layer's chunks are empty, so a chunk is only created when a
+      // non-empty tile is placed.
+      let (chunk_position, position_in_chunk) = Chunk::split_position(position);
+      let chunk =
+         self.chunks.entry(chunk_position).or_insert_with(|| Chunk::from_tile(Tile::EMPTY));
+      chunk[position_in_chunk] = tile;
+      #[cfg(feature = "game")]
+      self.invalidate_mesh(chunk_position);
+
+      // Auto-tiled neighbours may need their outlines updated to connect with the new tile.
+      for dy in -1..=1 {
+         for dx in -1..=1 {
+            self.update_outline((position.0 + dx, position.1 + dy), tileset);
          }
       }
 
-      Layer::Tile { chunks }
+      if let Some(collider) = self.tile_colliders.remove(&position) {
+         physics.remove_collider(collider);
+      }
+      self.build_tile_collider(position, tileset, physics);
+      if Self::solid_group(tileset, previous) != Self::solid_group(tileset, tile) {
+         self.remerge_solids_around(position, tileset, physics);
+      }
+
+      if self.chunks[&chunk_position].is_empty(tileset) {
+         self.chunks.remove(&chunk_position);
+      }
+      previous
+   }
+
+   /// Returns the smallest rectangle containing all of the layer's non-empty tiles, or `None` if
+   /// the layer doesn't have any tiles.
+   pub(super) fn bounds(&self) -> Option<Rect> {
+      self
+         .tiles()
+         .filter(|&(_, tile)| tile != Tile::EMPTY)
+         .map(|((x, y), _)| rect(vector(x as f32, y as f32), vector(1.0, 1.0)))
+         .reduce(|a, b| a.combine(&b))
+   }
+
+   /// Iterates over all tiles in the layer's chunks, along with their positions.
+   fn tiles(&self) -> impl Iterator<Item = ((i32, i32), Tile)> + '_ {
+      self.chunks.iter().flat_map(|(&(chunk_x, chunk_y), chunk)| {
+         (0..Chunk::SIZE).flat_map(move |y| {
+            (0..Chunk::SIZE).map(move |x| {
+               let position = (
+                  (chunk_x << Chunk::SIZE_BITS) + x as i32,
+                  (chunk_y << Chunk::SIZE_BITS) + y as i32,
+               );
+               (position, chunk[(x, y)])
+            })
+         })
+      })
+   }
+
+   /// Derives the outline of the tile at the given position from its neighbours, if it's an
+   /// auto-tiled solid block. The chunk's meshes are invalidated if the outline changes.
+   fn update_outline(&mut self, position: (i32, i32), tileset: &Tileset) {
+      let (chunk_position, position_in_chunk) = Chunk::split_position(position);
+      if !self.chunks.contains_key(&chunk_position) {
+         return;
+      }
+      let outline = if tileset.kind(self.tile_at(position).id) == TileKind::Solid {
+         Outline::from_neighbours(|dx, dy| {
+            let neighbour = self.tile_at((position.0 + dx, position.1 + dy));
+            tileset.kind(neighbour.id).is_solid()
+         })
+      } else {
+         Outline::EMPTY
+      };
+      let chunk = self.chunks.get_mut(&chunk_position).unwrap();
+      if chunk.outline(position_in_chunk) != outline {
+         chunk.set_outline(position_in_chunk, outline);
+         #[cfg(feature = "game")]
+         self.invalidate_mesh(chunk_position);
+      }
    }
 
-   /// Adds extra things (such as colliders) to tiles.
-   fn build_tile_extra(kind: TileKind, physics: &mut Physics, center: Vec2<f32>) {
+   /// Builds the collider of the tile at the given position, if its kind needs one. Solid tiles
+   /// are not handled here, see [`TileLayer::rebuild_solid_colliders`].
+   fn build_tile_collider(
+      &mut self,
+      position: (i32, i32),
+      tileset: &Tileset,
+      physics: &mut Physics,
+   ) {
       use TileKind::*;
 
-      match kind {
-         SpikesUp | SpikesDown | SpikesLeft | SpikesRight => {
-            Self::build_spikes_collider(kind.spike_direction().unwrap(), physics, center)
+      let tile = self.tile_at(position);
+      let kind = tileset.kind(tile.id);
+      let center = vector(position.0 as f32, position.1 as f32) + vector(0.5, 0.5);
+      let collider = match kind {
+         SpikesUp | SpikesDown | SpikesLeft | SpikesRight => Self::build_spikes_collider(
+            kind.spike_direction().unwrap().flipped(tile.flip),
+            physics,
+            center,
+         ),
+         | SlopeUp
+         | SlopeDown
+         | SlopeHalfUpLeft
+         | SlopeHalfUpRight
+         | SlopeHalfDownLeft
+         | SlopeHalfDownRight
+         | SlopeDoubleUpBottom
+         | SlopeDoubleUpTop
+         | SlopeDoubleDownBottom
+         | SlopeDoubleDownTop => {
+            Self::build_slope_collider(kind.slope_line().unwrap(), tile.flip, physics, center)
          }
-         _ => (),
+         _ => None,
+      };
+      if let Some(collider) = collider {
+         self.tile_colliders.insert(position, collider);
       }
    }
 
+   /// Adds a collider for a slope.
+   fn build_slope_collider(
+      line: SlopeLine,
+      flip: TileFlip,
+      physics: &mut Physics,
+      center: Vec2<f32>,
+   ) -> Option<ColliderHandle> {
+      let points: Vec<_> = line
+         .polygon(f32::INFINITY, flip)
+         .into_iter()
+         .map(|point| Point::from((center + point).nalgebra()))
+         .collect();
+      let collider = ColliderBuilder::convex_hull(&points)?
+         .collision_groups(InteractionGroups::new(
+            CollisionGroups::SOLIDS,
+            CollisionGroups::ALL,
+         ))
+         .build();
+      Some(physics.colliders.insert(collider))
+   }
+
    /// Adds a collider for spikes pointing at the given side.
-   fn build_spikes_collider(side: Side, physics: &mut Physics, center: Vec2<f32>) {
+   fn build_spikes_collider(
+      side: Side,
+      physics: &mut Physics,
+      center: Vec2<f32>,
+   ) -> Option<ColliderHandle> {
       // Half of the long side of the spikes.
       const LONG_HALF: f32 = 0.75 / 2.0;
       // Half of the short side of the spikes.
@@ -265,6 +757,157 @@ impl Loader {
             CollisionGroups::PLAYER,
          ))
          .build();
-      let _collider = physics.colliders.insert(collider);
+      Some(physics.colliders.insert(collider))
+   }
+
+   /// Returns the collision groups of the merged collider a tile is part of, or `None` if the
+   /// tile is not a solid tile or barrier.
+   fn solid_group(tileset: &Tileset, tile: Tile) -> Option<u32> {
+      match tileset.kind(tile.id) {
+         TileKind::Barrier => Some(tileset.barrier_group(tile.id)),
+         kind if kind.is_solid() => Some(CollisionGroups::SOLIDS),
+         _ => None,
+      }
+   }
+
+   /// Rebuilds the colliders for all solid tiles and barriers in the layer.
+   fn rebuild_solid_colliders(&mut self, tileset: &Tileset, physics: &mut Physics) {
+      for (_, collider) in self.solids.drain(..) {
+         physics.remove_collider(collider);
+      }
+      let solids = self.merge_solids(tileset);
+      self.insert_solid_colliders(&solids, physics);
+   }
+
+   /// Rebuilds the merged colliders touching the tile at the given position, after the tile has
+   /// changed. The tiles of the rectangles containing or bordering the tile are merged again
+   /// together with the tile itself, and the rest of the layer's colliders are left alone.
+   fn remerge_solids_around(
+      &mut self,
+      (x, y): (i32, i32),
+      tileset: &Tileset,
+      physics: &mut Physics,
+   ) {
+      let touched = [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+      let mut positions = vec![(x, y)];
+      let mut i = 0;
+      while i < self.solids.len() {
+         let (solid, _) = self.solids[i];
+         if touched.iter().any(|&position| solid.contains(position)) {
+            let (_, collider) = self.solids.swap_remove(i);
+            physics.remove_collider(collider);
+            positions.extend(solid.positions());
+         } else {
+            i += 1;
+         }
+      }
+
+      let groups = positions
+         .into_iter()
+         .filter_map(|position| {
+            Some((
+               position,
+               Self::solid_group(tileset, self.tile_at(position))?,
+            ))
+         })
+         .collect();
+      let solids = Self::merge_groups(groups);
+      self.insert_solid_colliders(&solids, physics);
+   }
+
+   /// Merges the layer's solid tiles and barriers into rectangles.
+   ///
+   /// Adjacent tiles with the same collision groups are merged into as few rectangles as
+   /// possible, so that the player doesn't catch on the seams between them. Rows are merged first,
+   /// so that floors and ceilings end up being a single collider wherever possible.
+   pub(super) fn merge_solids(&self, tileset: &Tileset) -> Vec<SolidRect> {
+      let groups = self
+         .tiles()
+         .filter_map(|(position, tile)| Some((position, Self::solid_group(tileset, tile)?)))
+         .collect();
+      Self::merge_groups(groups)
+   }
+
+   /// Merges tiles into rectangles, given the collision groups of the tiles at each position.
+   /// See [`TileLayer::merge_solids`].
+   fn merge_groups(mut groups: HashMap<(i32, i32), u32>) -> Vec<SolidRect> {
+      // Sort the tiles, such that each rectangle is started from its top left corner.
+      let mut tiles: Vec<_> = groups.keys().copied().collect();
+      tiles.sort_unstable_by_key(|&(x, y)| (y, x));
+
+      let mut solids = Vec::new();
+      for (left, top) in tiles {
+         let group = match groups.get(&(left, top)) {
+            Some(&group) => group,
+            // The tile was already merged into another rectangle.
+            None => continue,
+         };
+         let has_group = |position| groups.get(&position) == Some(&group);
+         let mut right = left + 1;
+         while has_group((right, top)) {
+            right += 1;
+         }
+         let mut bottom = top + 1;
+         while (left..right).all(|x| has_group((x, bottom))) {
+            bottom += 1;
+         }
+         for y in top..bottom {
+            for x in left..right {
+               groups.remove(&(x, y));
+            }
+         }
+         if group == 0 {
+            // Barriers that don't block anything don't need colliders.
+            continue;
+         }
+         solids.push(SolidRect {
+            left,
+            top,
+            right,
+            bottom,
+            group,
+         });
+      }
+      solids
+   }
+
+   /// Adds colliders for merged rectangles of solid tiles and barriers.
+   fn insert_solid_colliders(&mut self, solids: &[SolidRect], physics: &mut Physics) {
+      for solid in solids {
+         let half_extents = vector(
+            (solid.right - solid.left) as f32,
+            (solid.bottom - solid.top) as f32,
+         ) / 2.0;
+         let center = vector(solid.left as f32, solid.top as f32) + half_extents;
+         let collider = ColliderBuilder::cuboid(half_extents.x, half_extents.y)
+            .translation(center.nalgebra())
+            .collision_groups(InteractionGroups::new(solid.group, CollisionGroups::ALL))
+            .build();
+         self.solids.push((*solid, physics.colliders.insert(collider)));
+      }
+   }
+}
+
+/// A rectangle of solid tiles or barriers, merged into a single collider. The edges are in tile
+/// coordinates; `right` and `bottom` are exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct SolidRect {
+   pub left: i32,
+   pub top: i32,
+   pub right: i32,
+   pub bottom: i32,
+   /// The collision groups of the tiles.
+   pub group: u32,
+}
+
+impl SolidRect {
+   /// Returns whether the tile at the given position is part of the rectangle.
+   fn contains(&self, (x, y): (i32, i32)) -> bool {
+      (self.left..self.right).contains(&x) && (self.top..self.bottom).contains(&y)
+   }
+
+   /// Iterates over the positions of all tiles in the rectangle.
+   fn positions(self) -> impl Iterator<Item = (i32, i32)> {
+      (self.top..self.bottom).flat_map(move |y| (self.left..self.right).map(move |x| (x, y)))
    }
 }
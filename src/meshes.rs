@@ -5,7 +5,7 @@ use tetra::graphics::Color;
 use tetra::Context;
 use vek::Vec2;
 
-use crate::common::{colored_vertex, rect, vector, Rect, RectVectors};
+use crate::common::{colored_vertex, cross, rect, vector, Rect, RectVectors};
 
 /// A builder for meshes.
 pub struct MeshBuilder {
@@ -44,6 +44,64 @@ impl MeshBuilder {
       self
    }
 
+   /// Adds a convex polygon into the mesh.
+   pub fn convex_polygon(&mut self, points: &[Vec2<f32>], color: Color) -> &mut Self {
+      let first_index = self.vertices.len() as u32;
+      self.vertices.extend(points.iter().map(|&point| colored_vertex(point, color)));
+      for index in 1..(points.len() as u32).saturating_sub(1) {
+         self.indices.push(first_index);
+         self.indices.push(first_index + index);
+         self.indices.push(first_index + index + 1);
+      }
+      self
+   }
+
+   /// Adds a simple polygon into the mesh. Unlike [`MeshBuilder::convex_polygon`], the polygon may
+   /// be concave; it's triangulated by clipping its ears off one by one.
+   pub fn polygon(&mut self, points: &[Vec2<f32>], color: Color) -> &mut Self {
+      let first_index = self.vertices.len() as u32;
+      self.vertices.extend(points.iter().map(|&point| colored_vertex(point, color)));
+
+      // Whether a vertex is convex depends on the winding order of the polygon, so the order
+      // is normalized by multiplying by this sign.
+      let winding = (0..points.len())
+         .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+         .sum::<f32>()
+         .signum();
+      let turn = |a: usize, b: usize, c: usize| {
+         cross(points[b] - points[a], points[c] - points[b]) * winding
+      };
+
+      let mut remaining: Vec<usize> = (0..points.len()).collect();
+      while remaining.len() >= 3 {
+         let n = remaining.len();
+         let corners = |i: usize| {
+            (
+               remaining[(i + n - 1) % n],
+               remaining[i],
+               remaining[(i + 1) % n],
+            )
+         };
+         let is_ear = |i: usize| {
+            let (a, b, c) = corners(i);
+            turn(a, b, c) > 0.0
+               && remaining.iter().all(|&p| {
+                  [a, b, c].contains(&p)
+                     || turn(a, b, p) < 0.0
+                     || turn(b, c, p) < 0.0
+                     || turn(c, a, p) < 0.0
+               })
+         };
+         // Self-intersecting polygons may run out of ears, in which case any vertex is clipped so
+         // that triangulation still terminates.
+         let ear = (0..n).find(|&i| is_ear(i)).unwrap_or(0);
+         let (a, b, c) = corners(ear);
+         self.indices.extend([a, b, c].map(|index| first_index + index as u32));
+         remaining.remove(ear);
+      }
+      self
+   }
+
    /// Adds an arc into the mesh.
    pub fn arc(
       &mut self,
@@ -1,10 +1,10 @@
 //! An easy to use wrapper over Rapier.
 
 use rapier2d::prelude::{
-   BroadPhase, CCDSolver, ColliderSet, IntegrationParameters, IslandManager, JointSet, NarrowPhase,
-   PhysicsPipeline, QueryPipeline, RigidBodySet,
+   BroadPhase, CCDSolver, ColliderHandle, ColliderSet, IntegrationParameters, IslandManager,
+   JointSet, NarrowPhase, PhysicsPipeline, QueryPipeline, RigidBodySet,
 };
-use tetra::math::Vec2;
+use vek::Vec2;
 
 use crate::common::ToNalgebraVector2;
 
@@ -49,6 +49,16 @@ impl Physics {
       }
    }
 
+   /// Removes a collider, waking up any bodies touching it.
+   pub fn remove_collider(&mut self, collider: ColliderHandle) {
+      self.colliders.remove(
+         collider,
+         &mut self.island_manager,
+         &mut self.rigid_bodies,
+         true,
+      );
+   }
+
    /// Updates the query pipeline.
    pub fn update_query_pipeline(&mut self) {
       self.query.update(&self.island_manager, &self.rigid_bodies, &self.colliders);
@@ -89,10 +99,16 @@ impl CollisionGroups {
    pub const TRIGGERS: u32     = 0b00001000_00000000;
    pub const CAMERA_VIEWS: u32 = 0b10000000_00000000;
 
+   // Barriers are solid only to players with specific morphs, so each morph gets its own group.
+   pub const UNSHAPED_BARRIERS: u32   = 0b00010000_00000000;
+   pub const PLATFORMER_BARRIERS: u32 = 0b00100000_00000000;
+   pub const BARRIERS: u32 = Self::UNSHAPED_BARRIERS | Self::PLATFORMER_BARRIERS;
+
    pub const ALL: u32 =
       Self::PLAYER
       | Self::SOLIDS
       | Self::DEADLY
       | Self::MORPH_ZONES
-      | Self::CAMERA_VIEWS;
+      | Self::CAMERA_VIEWS
+      | Self::BARRIERS;
 }
@@ -4,6 +4,7 @@ use std::any::{Any, TypeId};
 use std::collections::HashMap;
 
 /// A resource map.
+#[derive(Default)]
 pub struct Resources {
    map: HashMap<TypeId, Box<dyn Any>>,
 }
@@ -40,6 +41,14 @@ impl Resources {
       self.map.get_mut(&TypeId::of::<T>()).map(|value| value.downcast_mut().unwrap())
    }
 
+   /// Removes the resource of the given type from the map, and returns it.
+   pub fn remove<T>(&mut self) -> Option<T>
+   where
+      T: Any,
+   {
+      self.map.remove(&TypeId::of::<T>()).map(|value| *value.downcast().unwrap())
+   }
+
    /// Executes a function to insert a value into the resource map, if not already
    /// in there.
    pub fn get_or_insert<T, E>(&mut self, f: impl FnOnce() -> T) -> &mut T
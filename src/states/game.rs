@@ -1,24 +1,33 @@
 //! The state in which you play the game.
 
 use hecs::{Entity, World};
+use log::{error, info, warn};
+use rapier2d::math::Isometry;
+use rapier2d::prelude::{Ball, InteractionGroups};
+use tetra::audio::{Sound, SoundInstance};
 use tetra::graphics::{Color, DrawParams, StencilAction, StencilState, StencilTest, Texture};
 use tetra::math::Vec2;
 use tetra::{graphics, window, Context};
 
 use crate::assets::RemappableColors;
 use crate::common::{
-   load_asset, load_asset_to_string, rect, vector, window_size, Rect, RectVectors,
+   asset_path, load_asset, load_asset_to_string, rect, vector, window_size, Rect, RectVectors,
 };
 use crate::entities::camera::Camera;
+use crate::entities::checkpoint::RespawnPosition;
+use crate::entities::out_of_bounds::OutOfBounds;
 use crate::entities::player::Player;
-use crate::input::Input;
-use crate::map::Map;
+use crate::entities::zones::Zones;
+use crate::entities::Position;
+use crate::input::{Button, Input};
+use crate::map::{Map, MapSettings, MeshBatching, ObjectRegistry};
 use crate::meshes::MeshBuilder;
-use crate::physics::Physics;
+use crate::physics::{CollisionGroups, Physics};
 use crate::post_process::{PixelEffect, PostProcess};
 use crate::resources::Resources;
 use crate::state::GameState;
 use crate::transform::TransformStack;
+use crate::watcher::FileWatcher;
 use crate::{entities, transform};
 
 /// The state.
@@ -26,6 +35,11 @@ pub struct State {
    world: World,
    physics: Physics,
    map: Map,
+   /// The map's music. Dropping the instance doesn't stop the music, so it's kept around to be able
+   /// to control playback.
+   music: Option<SoundInstance>,
+   /// Watches the map's files, so that the map can be reloaded when they change.
+   map_watcher: FileWatcher,
 
    tstack: TransformStack,
    post_process: PostProcess,
@@ -40,26 +54,21 @@ impl State {
    /// The percentage of padding to leave along the window's sides.
    const WINDOW_PADDING_PERCENTAGE: f32 = 0.1;
 
-   pub fn new(ctx: &mut Context) -> anyhow::Result<Self> {
+   pub fn new(ctx: &mut Context, resources: &mut Resources) -> anyhow::Result<Self> {
       let mut world = World::new();
-      let mut physics = Physics::new(Vec2::new(0.0, 40.0));
-      let map = Map::load_into_world_from_json(
-         &mut world,
-         &mut physics,
-         &load_asset_to_string("generated/tileset.json")?,
-         &load_asset_to_string("generated/map.json")?,
-      )?;
-      let player = world
-         .query_mut::<&Player>()
-         .into_iter()
-         .next()
-         .map(|(id, _)| id)
-         .ok_or_else(|| anyhow::anyhow!("the map does not have a player"))?;
+      let mut physics = Physics::new(MapSettings::DEFAULT_GRAVITY);
+      let map = Self::load_map(ctx, resources, &mut world, &mut physics)?;
+      physics.gravity = map.settings.gravity;
+      Self::update_title(ctx, &map.settings);
+      let music = Self::play_map_music(ctx, &map.settings);
+      let player = Self::find_player(&mut world)?;
 
       Ok(Self {
          world,
          physics,
+         map_watcher: FileWatcher::new(map.sources.clone()),
          map,
+         music,
 
          tstack: TransformStack::new(),
          post_process: Self::resize_post_process(ctx)?,
@@ -71,6 +80,146 @@ impl State {
       })
    }
 
+   /// Loads the map into the world. The compiled map is preferred, as it loads much faster; if
+   /// it's missing, stale, or can't be read, the map is loaded from its sources instead.
+   fn load_map(
+      ctx: &mut Context,
+      resources: &mut Resources,
+      world: &mut World,
+      physics: &mut Physics,
+   ) -> anyhow::Result<Map> {
+      let compiled = Map::compiled_path();
+      let path = Map::source_path();
+      if compiled.is_file() {
+         info!("loading compiled map from {:?}", compiled);
+         match Map::load_compiled_into_world(ctx, resources, world, physics, &compiled) {
+            Ok(map) => return Ok(map),
+            // Builds that don't ship with `res/` have nothing to fall back to.
+            Err(error) if !path.is_file() => return Err(error),
+            Err(error) => warn!("{:#}; falling back to the map's sources", error),
+         }
+      } else if !path.is_file() {
+         anyhow::bail!(
+            "the compiled map {:?} is missing, and the map's sources are not available; \
+             compile the map with `cargo run --bin memphlus-assets`",
+            compiled
+         );
+      }
+      info!("loading map from {:?}", path);
+      Map::load_into_world(ctx, resources, world, physics, &path)
+   }
+
+   /// Reloads the map from its files, rebuilding the world and physics. The player is placed back
+   /// where they were, and keeps their respawn position if it's still valid. The camera carries on
+   /// from where it was, so it doesn't jump.
+   fn reload_map(&mut self, ctx: &mut Context, resources: &mut Resources) -> anyhow::Result<()> {
+      let Position(position) = *self.world.get::<Position>(self.player)?;
+      let RespawnPosition(respawn_position) = *self.world.get::<RespawnPosition>(self.player)?;
+      let camera = Camera::get(&mut self.world, self.player);
+
+      let mut world = World::new();
+      let mut physics = Physics::new(MapSettings::DEFAULT_GRAVITY);
+      // The new map's resources are kept aside until the map is known to be playable, so that a
+      // failed reload doesn't leave behind resources referring to the discarded world.
+      let mut map_resources = Resources::new();
+      let map = Self::load_map(ctx, &mut map_resources, &mut world, &mut physics)?;
+      physics.gravity = map.settings.gravity;
+      physics.update_query_pipeline();
+      let player = Self::find_player(&mut world)?;
+
+      // The map may have changed around the player, so their old positions can now be inside of a
+      // wall or out of bounds.
+      let RespawnPosition(spawn_position) = *world.get::<RespawnPosition>(player)?;
+      let is_free = |position| Self::is_free(&world, &physics, position);
+      let respawn_position = if is_free(respawn_position) {
+         respawn_position
+      } else {
+         spawn_position
+      };
+      let position = if is_free(position) {
+         position
+      } else {
+         respawn_position
+      };
+      world.get_mut::<RespawnPosition>(player)?.0 = respawn_position;
+      Player::teleport(&mut world, &mut physics, player, position);
+      *world.get_mut::<Camera>(player)? = Camera {
+         // The view belongs to the old world, so it has to be looked up again.
+         view: None,
+         ..camera
+      };
+      if let Some(registry) = map_resources.remove::<ObjectRegistry>() {
+         resources.insert(registry);
+      }
+
+      if map.settings.music != self.map.settings.music {
+         if let Some(music) = &self.music {
+            music.stop();
+         }
+         self.music = Self::play_map_music(ctx, &map.settings);
+      }
+      Self::update_title(ctx, &map.settings);
+
+      self.map_watcher = FileWatcher::new(map.sources.clone());
+      self.world = world;
+      self.physics = physics;
+      self.map = map;
+      self.player = player;
+      Ok(())
+   }
+
+   /// Checks whether the player can be placed at the given position without ending up inside of a
+   /// wall, inside of something deadly, or out of bounds.
+   fn is_free(world: &World, physics: &Physics, position: Vec2<f32>) -> bool {
+      OutOfBounds::is_in_bounds(world, position)
+         && physics
+            .query
+            .intersection_with_shape(
+               &physics.colliders,
+               &Isometry::translation(position.x, position.y),
+               &Ball::new(0.01),
+               InteractionGroups::new(
+                  CollisionGroups::PLAYER,
+                  CollisionGroups::SOLIDS | CollisionGroups::DEADLY,
+               ),
+               None,
+            )
+            .is_none()
+   }
+
+   /// Returns the player entity.
+   fn find_player(world: &mut World) -> anyhow::Result<Entity> {
+      world
+         .query_mut::<&Player>()
+         .into_iter()
+         .next()
+         .map(|(id, _)| id)
+         .ok_or_else(|| anyhow::anyhow!("the map does not have a player"))
+   }
+
+   /// Sets the window's title to include the map's name.
+   fn update_title(ctx: &mut Context, settings: &MapSettings) {
+      match &settings.name {
+         Some(name) => window::set_title(ctx, format!("mem.pHlus - {}", name)),
+         None => window::set_title(ctx, "mem.pHlus"),
+      }
+   }
+
+   /// Starts playing the map's music, if it has any. Errors are logged rather than returned, as
+   /// the game is perfectly playable without music.
+   fn play_map_music(ctx: &mut Context, settings: &MapSettings) -> Option<SoundInstance> {
+      let music = settings.music.as_ref()?;
+      Self::play_music(ctx, music)
+         .map_err(|error| error!("cannot play music {:?}: {}", music, error))
+         .ok()
+   }
+
+   /// Starts playing a music track on repeat.
+   fn play_music(ctx: &mut Context, path: &str) -> anyhow::Result<SoundInstance> {
+      let sound = Sound::new(asset_path(path))?;
+      Ok(sound.repeat(ctx)?)
+   }
+
    /// Creates a new PostProcess with the window's size.
    fn resize_post_process(ctx: &mut Context) -> anyhow::Result<PostProcess> {
       let (width, height) = window::get_size(ctx);
@@ -84,8 +233,8 @@ impl State {
    }
 
    /// Applies the camera transform to the graphics context and returns the screen-space rectangle
-   /// the camera is viewing.
-   fn apply_camera_transform(&mut self, ctx: &mut Context) -> Rect {
+   /// the camera is viewing, along with the world-space rectangle visible through it.
+   fn apply_camera_transform(&mut self, ctx: &mut Context) -> (Rect, Rect) {
       let camera = Camera::get(&mut self.world, self.player).blend(ctx);
       let window_size = window_size(ctx);
       let padded_window_size = window_size - Self::window_padding(ctx);
@@ -101,24 +250,34 @@ impl State {
 
       let camera_size = camera.size() * scale;
       let camera_position = window_size / 2.0 - camera_size / 2.0;
-      rect(camera_position, camera_size)
+      (rect(camera_position, camera_size), camera)
    }
 
    /// Draws the world. Returns the screen-space camera rectangle.
    fn draw_world(&mut self, ctx: &mut Context, resources: &mut Resources) -> anyhow::Result<Rect> {
-      graphics::clear(ctx, RemappableColors::BACKGROUND);
+      // The blue channel selects the palette, so clearing sets the palette used outside of zones.
+      graphics::clear(
+         ctx,
+         Color {
+            b: self.map.settings.palette as f32 / Zones::MAX as f32,
+            ..RemappableColors::BACKGROUND
+         },
+      );
 
       self.tstack.save(ctx);
-      let camera_rect = self.apply_camera_transform(ctx);
+      let (camera_rect, viewport) = self.apply_camera_transform(ctx);
 
-      self.map.draw(ctx, &mut self.tstack)?;
+      self.map.draw_background(ctx, viewport)?;
       entities::draw_systems(
          ctx,
          &mut self.tstack,
          resources,
          &mut self.world,
          &mut self.physics,
+         viewport,
+         self.map.tile_size(),
       )?;
+      self.map.draw_foreground(ctx, viewport)?;
 
       self.tstack.restore(ctx);
 
@@ -130,9 +289,24 @@ impl GameState for State {
    fn update(
       &mut self,
       ctx: &mut Context,
-      _resources: &mut Resources,
+      resources: &mut Resources,
       input: &Input,
    ) -> anyhow::Result<()> {
+      if self.map_watcher.changed() {
+         info!("map files changed, reloading");
+         if let Err(error) = self.reload_map(ctx, resources) {
+            error!("cannot reload map: {:#}", error);
+         }
+      }
+
+      if input.button_just_pressed(ctx, Button::ToggleMeshBatching) {
+         self.map.mesh_batching = match self.map.mesh_batching {
+            MeshBatching::Chunks => MeshBatching::Regions,
+            MeshBatching::Regions => MeshBatching::Chunks,
+         };
+         info!("map mesh batching: {:?}", self.map.mesh_batching);
+      }
+
       entities::tick_systems(ctx, &mut self.world, &mut self.physics, input);
       self.physics.step();
       Ok(())
@@ -0,0 +1,66 @@
+//! Decoding of tile layer data.
+
+use std::io::Read;
+
+use anyhow::Context;
+use flate2::read::{GzDecoder, ZlibDecoder};
+
+use super::Gid;
+
+/// Decodes comma-separated tile data. Data without any tiles decodes to no tiles.
+fn decode_csv(csv: &str) -> anyhow::Result<Vec<Gid>> {
+   if csv.trim().is_empty() {
+      return Ok(Vec::new());
+   }
+   csv.split(',')
+      .map(|tile| tile.trim().parse().with_context(|| format!("invalid tile {:?}", tile)))
+      .collect()
+}
+
+/// Decodes base64 tile data, decompressing it if needed. The decoded data is an array of
+/// little-endian 32-bit GIDs.
+fn decode_base64(data: &str, compression: &str) -> anyhow::Result<Vec<Gid>> {
+   let bytes = base64::decode(data.trim()).context("tile data is not valid base64")?;
+   let bytes = match compression {
+      "" => bytes,
+      "zlib" => {
+         let mut decompressed = Vec::new();
+         ZlibDecoder::new(&bytes[..])
+            .read_to_end(&mut decompressed)
+            .context("cannot decompress zlib tile data")?;
+         decompressed
+      }
+      "gzip" => {
+         let mut decompressed = Vec::new();
+         GzDecoder::new(&bytes[..])
+            .read_to_end(&mut decompressed)
+            .context("cannot decompress gzip tile data")?;
+         decompressed
+      }
+      other => anyhow::bail!("unsupported tile data compression {:?}", other),
+   };
+   if bytes.len() % 4 != 0 {
+      anyhow::bail!(
+         "tile data length ({} bytes) is not a multiple of 4",
+         bytes.len()
+      );
+   }
+   Ok(bytes
+      .chunks_exact(4)
+      .map(|tile| Gid(u32::from_le_bytes([tile[0], tile[1], tile[2], tile[3]])))
+      .collect())
+}
+
+/// Decodes tile data stored as a string, with the given encoding (`csv` or `base64`) and
+/// compression (empty, `zlib` or `gzip`).
+pub(super) fn decode_tiles(
+   data: &str,
+   encoding: &str,
+   compression: &str,
+) -> anyhow::Result<Vec<Gid>> {
+   match encoding {
+      "csv" => decode_csv(data),
+      "base64" => decode_base64(data, compression),
+      other => anyhow::bail!("unsupported tile data encoding {:?}", other),
+   }
+}
@@ -0,0 +1,1104 @@
+//! Minimal Tiled map loader, supporting both JSON exports and the native XML formats.
+
+mod encoding;
+mod xml;
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use bitflags::bitflags;
+use serde::de::{Error, IntoDeserializer, Visitor};
+use serde::Deserialize;
+use vek::{Rgba, Vec2};
+
+pub type TileId = u16;
+
+bitflags! {
+   /// The ways a tile can be flipped. Rotations are expressed as combinations of these flips.
+   ///
+   /// When transforming a tile, the diagonal flip (which swaps the X and Y axes) is applied first,
+   /// followed by the horizontal and vertical flips.
+   pub struct TileFlip: u8 {
+      const HORIZONTAL = 0b001;
+      const VERTICAL = 0b010;
+      const DIAGONAL = 0b100;
+   }
+}
+
+impl TileFlip {
+   /// Transforms a vector relative to the center of a tile.
+   pub fn apply(self, vector: Vec2<f32>) -> Vec2<f32> {
+      let mut vector = vector;
+      if self.contains(Self::DIAGONAL) {
+         vector = Vec2::new(vector.y, vector.x);
+      }
+      if self.contains(Self::HORIZONTAL) {
+         vector.x = -vector.x;
+      }
+      if self.contains(Self::VERTICAL) {
+         vector.y = -vector.y;
+      }
+      vector
+   }
+}
+
+/// A global tile ID, as stored in tile layers. Apart from the tile's ID, the upper bits of a GID
+/// store the ways the tile is flipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Gid(pub u32);
+
+impl Gid {
+   const FLIPPED_HORIZONTALLY: u32 = 0x80000000;
+   const FLIPPED_VERTICALLY: u32 = 0x40000000;
+   const FLIPPED_DIAGONALLY: u32 = 0x20000000;
+   /// Only used by hexagonal maps, but it still needs to be masked out.
+   const ROTATED_HEXAGONAL_120: u32 = 0x10000000;
+   const FLAGS: u32 = Self::FLIPPED_HORIZONTALLY
+      | Self::FLIPPED_VERTICALLY
+      | Self::FLIPPED_DIAGONALLY
+      | Self::ROTATED_HEXAGONAL_120;
+
+   /// Returns the GID with its flip flags cleared.
+   pub fn tile_id(self) -> u32 {
+      self.0 & !Self::FLAGS
+   }
+
+   /// Returns the ways the tile is flipped.
+   pub fn flip(self) -> TileFlip {
+      let mut flip = TileFlip::empty();
+      flip.set(
+         TileFlip::HORIZONTAL,
+         self.0 & Self::FLIPPED_HORIZONTALLY != 0,
+      );
+      flip.set(TileFlip::VERTICAL, self.0 & Self::FLIPPED_VERTICALLY != 0);
+      flip.set(TileFlip::DIAGONAL, self.0 & Self::FLIPPED_DIAGONALLY != 0);
+      flip
+   }
+}
+
+impl FromStr for Gid {
+   type Err = std::num::ParseIntError;
+
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      Ok(Self(s.parse()?))
+   }
+}
+
+pub type ObjectId = u32;
+
+/// The value of a property.
+#[derive(Debug, Clone)]
+pub enum PropertyValue {
+   String(String),
+   Int(i32),
+   Float(f32),
+   Bool(bool),
+   /// A color. Colors that are left unset in the editor are transparent black.
+   Color(Rgba<u8>),
+   /// A path to a file, relative to the file the property is stored in.
+   File(String),
+   Object(ObjectId),
+   /// An instance of a custom class, whose members are stored as properties.
+   Class(Properties),
+   /// A value of a custom enum.
+   Enum {
+      /// The name of the enum.
+      enum_type: String,
+      value: EnumValue,
+   },
+}
+
+/// The value of a custom enum property. Which variant is used depends on how the enum is
+/// configured to be stored in the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnumValue {
+   /// The name of the chosen value.
+   Name(String),
+   /// The index of the chosen value, or a bitmask of the chosen values if the enum allows for
+   /// choosing multiple values.
+   Int(i32),
+}
+
+impl PropertyValue {
+   /// Parses a property value from its textual representation. `property_type` is the name of the
+   /// custom type the property is of, which is used to tell enums apart from plain strings and
+   /// ints. Classes don't have a textual representation, so they have to be parsed separately.
+   fn parse(kind: &str, property_type: Option<&str>, value: &str) -> anyhow::Result<Self> {
+      let invalid = || format!("invalid {} value {:?}", kind, value);
+      Ok(match (kind, property_type) {
+         ("string", Some(enum_type)) => Self::Enum {
+            enum_type: enum_type.to_owned(),
+            value: EnumValue::Name(value.to_owned()),
+         },
+         ("int", Some(enum_type)) => Self::Enum {
+            enum_type: enum_type.to_owned(),
+            value: EnumValue::Int(value.parse().with_context(invalid)?),
+         },
+         ("string", None) => Self::String(value.to_owned()),
+         ("int", None) => Self::Int(value.parse().with_context(invalid)?),
+         ("float", _) => Self::Float(value.parse().with_context(invalid)?),
+         ("bool", _) => Self::Bool(value.parse().with_context(invalid)?),
+         ("color", _) => Self::Color(parse_color(value).with_context(invalid)?),
+         ("file", _) => Self::File(value.to_owned()),
+         ("object", _) => Self::Object(value.parse().with_context(invalid)?),
+         (other, _) => anyhow::bail!("unsupported property type {:?}", other),
+      })
+   }
+
+   /// Decodes a property value from a JSON file.
+   fn from_json(
+      kind: &str,
+      property_type: Option<&str>,
+      value: serde_json::Value,
+   ) -> anyhow::Result<Self> {
+      match (kind, value) {
+         ("class", serde_json::Value::Object(members)) => Self::class_from_json(members),
+         ("class", serde_json::Value::Null) => Ok(Self::Class(Properties::default())),
+         ("class", other) => anyhow::bail!("invalid class value {}", other),
+         (kind, serde_json::Value::String(value)) => Self::parse(kind, property_type, &value),
+         (kind, value) => Self::parse(kind, property_type, &value.to_string()),
+      }
+   }
+
+   /// Decodes the members of a class from a JSON file. Unlike properties, class members are
+   /// stored without their types, so the types are inferred from the JSON values: colors, files,
+   /// and enums are read as strings, and whole floats are read as ints.
+   fn class_from_json(members: serde_json::Map<String, serde_json::Value>) -> anyhow::Result<Self> {
+      let members = members
+         .into_iter()
+         .map(|(name, value)| {
+            let value = match value {
+               serde_json::Value::Bool(value) => Self::Bool(value),
+               serde_json::Value::Number(number) => match number.as_i64() {
+                  Some(int) if i32::try_from(int).is_ok() => Self::Int(int as i32),
+                  _ => Self::Float(number.as_f64().unwrap_or_default() as f32),
+               },
+               serde_json::Value::String(value) => Self::String(value),
+               serde_json::Value::Object(members) => Self::class_from_json(members)
+                  .with_context(|| format!("in class member {:?}", name))?,
+               other => anyhow::bail!("class member {:?} has invalid value {}", name, other),
+            };
+            Ok((name, value))
+         })
+         .collect::<anyhow::Result<_>>()?;
+      Ok(Self::Class(Properties(members)))
+   }
+
+   pub fn as_int(&self) -> Option<i32> {
+      if let Self::Int(v) = self {
+         Some(*v)
+      } else {
+         None
+      }
+   }
+
+   /// Returns the property's value as a float. Ints are converted to floats, since members of
+   /// classes stored in JSON files don't remember whether they were floats.
+   pub fn as_float(&self) -> Option<f32> {
+      match self {
+         Self::Float(v) => Some(*v),
+         Self::Int(v) => Some(*v as f32),
+         _ => None,
+      }
+   }
+
+   pub fn as_bool(&self) -> Option<bool> {
+      if let Self::Bool(v) = self {
+         Some(*v)
+      } else {
+         None
+      }
+   }
+
+   pub fn as_str(&self) -> Option<&str> {
+      if let Self::String(s) = self {
+         Some(s)
+      } else {
+         None
+      }
+   }
+
+   pub fn as_color(&self) -> Option<Rgba<u8>> {
+      if let Self::Color(color) = self {
+         Some(*color)
+      } else {
+         None
+      }
+   }
+
+   pub fn as_file(&self) -> Option<&str> {
+      if let Self::File(path) = self {
+         Some(path)
+      } else {
+         None
+      }
+   }
+
+   pub fn as_object(&self) -> Option<ObjectId> {
+      if let Self::Object(id) = self {
+         Some(*id)
+      } else {
+         None
+      }
+   }
+
+   pub fn as_class(&self) -> Option<&Properties> {
+      if let Self::Class(members) = self {
+         Some(members)
+      } else {
+         None
+      }
+   }
+
+   /// Returns the name of the enum and the chosen value, if the property is an enum.
+   pub fn as_enum(&self) -> Option<(&str, &EnumValue)> {
+      if let Self::Enum { enum_type, value } = self {
+         Some((enum_type, value))
+      } else {
+         None
+      }
+   }
+}
+
+/// Parses a color in Tiled's `#AARRGGBB` or `#RRGGBB` format. An empty string is an unset color,
+/// which is parsed as transparent black.
+fn parse_color(color: &str) -> anyhow::Result<Rgba<u8>> {
+   if color.is_empty() {
+      return Ok(Rgba::zero());
+   }
+   let hex = color.strip_prefix('#').unwrap_or(color);
+   if !hex.is_ascii() {
+      anyhow::bail!("colors must be hexadecimal");
+   }
+   let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+   Ok(match hex.len() {
+      6 => Rgba::new(channel(0)?, channel(2)?, channel(4)?, 255),
+      8 => Rgba::new(channel(2)?, channel(4)?, channel(6)?, channel(0)?),
+      _ => anyhow::bail!("colors must have 6 or 8 hexadecimal digits"),
+   })
+}
+
+/// Storage for properties.
+#[derive(Debug, Clone)]
+pub struct Properties(HashMap<String, PropertyValue>);
+
+/// Properties dereference to a `HashMap<String, PropertyValue>`.
+impl Deref for Properties {
+   type Target = HashMap<String, PropertyValue>;
+
+   fn deref(&self) -> &Self::Target {
+      &self.0
+   }
+}
+
+/// Properties can be collected from pairs of names and values.
+impl FromIterator<(String, PropertyValue)> for Properties {
+   fn from_iter<T: IntoIterator<Item = (String, PropertyValue)>>(iter: T) -> Self {
+      Self(iter.into_iter().collect())
+   }
+}
+
+impl<'de> Deserialize<'de> for Properties {
+   fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+   where
+      D: serde::Deserializer<'de>,
+   {
+      #[derive(Debug, Deserialize)]
+      struct Property {
+         name: String,
+         #[serde(rename = "type", default = "Property::default_type")]
+         kind: String,
+         #[serde(rename = "propertytype")]
+         property_type: Option<String>,
+         #[serde(default)]
+         value: serde_json::Value,
+      }
+
+      impl Property {
+         fn default_type() -> String {
+            "string".into()
+         }
+      }
+
+      struct PropertyVisitor {
+         properties: HashMap<String, PropertyValue>,
+      }
+
+      impl<'de> Visitor<'de> for PropertyVisitor {
+         type Value = HashMap<String, PropertyValue>;
+
+         fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("property array")
+         }
+
+         fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
+         where
+            A: serde::de::SeqAccess<'de>,
+         {
+            while let Some(item) = seq.next_element::<Property>()? {
+               let value =
+                  PropertyValue::from_json(&item.kind, item.property_type.as_deref(), item.value)
+                     .map_err(|error| {
+                     A::Error::custom(format!("property {:?}: {:#}", item.name, error))
+                  })?;
+               self.properties.insert(item.name, value);
+            }
+            Ok(self.properties)
+         }
+      }
+
+      deserializer
+         .deserialize_seq(PropertyVisitor {
+            properties: HashMap::new(),
+         })
+         .map(|hash_map| Properties(hash_map))
+   }
+}
+
+impl Default for Properties {
+   fn default() -> Self {
+      Self(Default::default())
+   }
+}
+
+/// A tile specification - its ID and kind, as specified in the editor.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tile {
+   /// The unique ID of the tile.
+   pub id: TileId,
+   /// The kind of tile, as specified in the editor. Tiled 1.9 calls this the tile's class.
+   #[serde(rename = "type", alias = "class")]
+   pub kind: String,
+   /// The objects that make up the tile's collision.
+   #[serde(rename = "objectgroup")]
+   pub object_group: Option<LayerKind>,
+   /// The custom properties of the tile.
+   #[serde(default)]
+   pub properties: Properties,
+}
+
+/// A tileset.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tileset {
+   /// The width of tiles in the tileset.
+   #[serde(rename = "tilewidth")]
+   pub tile_width: u32,
+   /// The height of tiles in the tileset.
+   #[serde(rename = "tileheight")]
+   pub tile_height: u32,
+
+   /// The total number of tiles in the tileset. This is not the same as `tiles.len()`, because
+   /// this is `maximum_tile_id + 1`. Might be convenient for allocations.
+   #[serde(rename = "tilecount")]
+   pub tile_count: u32,
+   pub tiles: Vec<Tile>,
+}
+
+impl Tileset {
+   /// Loads a tileset from JSON data.
+   pub fn load_from_json(json: &str) -> anyhow::Result<Self> {
+      Ok(serde_json::from_str(json)?)
+   }
+
+   /// Loads a tileset from a file, picking the format based on the file's extension. XML tilesets
+   /// (`.tsx`) and JSON tilesets (`.tsj`, `.json`) are supported.
+   pub fn load(path: &Path) -> anyhow::Result<Self> {
+      match path.extension().and_then(|extension| extension.to_str()) {
+         Some("tsx") => Self::load_from_tsx(path),
+         Some("tsj" | "json") => {
+            let json =
+               std::fs::read_to_string(path).with_context(|| format!("cannot read {:?}", path))?;
+            Self::load_from_json(&json).with_context(|| format!("invalid tileset {:?}", path))
+         }
+         _ => anyhow::bail!("tileset {:?} has an unknown file format", path),
+      }
+   }
+}
+
+/// A tileset used by a map, as stored in the map file. Tilesets can either be embedded in the map,
+/// or stored in separate files.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "EncodedTilesetRef")]
+enum TilesetRef {
+   External {
+      #[serde(rename = "firstgid")]
+      first_gid: u32,
+      source: String,
+   },
+   Embedded {
+      #[serde(rename = "firstgid")]
+      first_gid: u32,
+      #[serde(flatten)]
+      tileset: Tileset,
+   },
+}
+
+/// A tileset reference, as stored in a JSON map. References with a `source` are external
+/// tilesets; otherwise the tileset's fields are stored alongside `firstgid`.
+#[derive(Debug, Clone, Deserialize)]
+struct EncodedTilesetRef {
+   #[serde(rename = "firstgid")]
+   first_gid: u32,
+   source: Option<String>,
+   #[serde(flatten)]
+   tileset: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TryFrom<EncodedTilesetRef> for TilesetRef {
+   type Error = String;
+
+   fn try_from(tileset: EncodedTilesetRef) -> Result<Self, Self::Error> {
+      let first_gid = tileset.first_gid;
+      match tileset.source {
+         Some(source) => Ok(Self::External { first_gid, source }),
+         None => Ok(Self::Embedded {
+            first_gid,
+            tileset: Tileset::deserialize(serde_json::Value::Object(tileset.tileset)).map_err(
+               |error| {
+                  format!(
+                     "invalid embedded tileset with firstgid {}: {}",
+                     first_gid, error
+                  )
+               },
+            )?,
+         }),
+      }
+   }
+}
+
+impl TilesetRef {
+   /// Loads the referenced tileset. External tilesets are resolved relative to `directory`.
+   fn load(self, directory: &Path) -> anyhow::Result<MapTileset> {
+      match self {
+         Self::External { first_gid, source } => {
+            let source = directory.join(source);
+            Ok(MapTileset {
+               first_gid,
+               tileset: Tileset::load(&source).with_context(|| {
+                  format!(
+                     "cannot load tileset {:?} with firstgid {}",
+                     source, first_gid
+                  )
+               })?,
+               source: Some(source),
+            })
+         }
+         Self::Embedded { first_gid, tileset } => Ok(MapTileset {
+            first_gid,
+            tileset,
+            source: None,
+         }),
+      }
+   }
+}
+
+/// A tileset used by a map.
+#[derive(Debug, Clone)]
+pub struct MapTileset {
+   /// The global ID of the tileset's first tile. The tileset's tiles occupy the global IDs
+   /// `first_gid..first_gid + tile_count`.
+   pub first_gid: u32,
+   pub tileset: Tileset,
+   /// The path to the tileset's file, or `None` if the tileset is embedded in the map.
+   pub source: Option<PathBuf>,
+}
+
+/// Tile data, as stored in a JSON map. Depending on the layer's encoding, this is either an array of
+/// tile IDs, or a string.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum EncodedTiles {
+   Tiles(Vec<Gid>),
+   Encoded(String),
+}
+
+impl EncodedTiles {
+   /// Decodes the tiles using the given encoding and compression.
+   fn decode(self, encoding: &str, compression: &str) -> anyhow::Result<Vec<Gid>> {
+      match self {
+         Self::Tiles(tiles) => Ok(tiles),
+         Self::Encoded(data) => encoding::decode_tiles(&data, encoding, compression),
+      }
+   }
+}
+
+/// A chunk of tiles, as stored in a JSON map.
+#[derive(Debug, Clone, Deserialize)]
+struct EncodedChunk {
+   data: EncodedTiles,
+   x: i32,
+   y: i32,
+   width: u32,
+   height: u32,
+}
+
+/// A tile layer, as stored in a JSON map.
+#[derive(Debug, Clone, Deserialize)]
+struct EncodedTileLayer {
+   #[serde(default)]
+   chunks: Vec<EncodedChunk>,
+   data: Option<EncodedTiles>,
+   #[serde(default)]
+   width: u32,
+   #[serde(default)]
+   height: u32,
+   #[serde(default)]
+   encoding: Option<String>,
+   #[serde(default)]
+   compression: String,
+}
+
+/// A chunk of tiles. Tiles are stored in rows of `width` tiles, and the chunk's position is
+/// expressed in tiles.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chunk {
+   pub data: Vec<Gid>,
+   pub x: i32,
+   pub y: i32,
+   pub width: u32,
+   pub height: u32,
+}
+
+/// The tiles of a tile layer.
+///
+/// Infinite layers store their tiles in chunks, whereas finite layers store all of their tiles
+/// in `data`, in rows of `width` tiles.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "EncodedTileLayer")]
+pub struct TileLayer {
+   pub chunks: Vec<Chunk>,
+   pub data: Vec<Gid>,
+   pub width: u32,
+   pub height: u32,
+}
+
+impl TileLayer {
+   /// Checks that the amount of tiles in the layer's chunks, or the layer itself if it's finite,
+   /// matches their size.
+   fn validate(&self) -> anyhow::Result<()> {
+      let check_length = |what: &dyn Fn() -> String, width: u32, height: u32, length: usize| {
+         let expected_length = width as usize * height as usize;
+         if length != expected_length {
+            anyhow::bail!(
+               "{} of size {}x{} should have {} tiles, but has {}",
+               what(),
+               width,
+               height,
+               expected_length,
+               length
+            );
+         }
+         Ok(())
+      };
+      if !self.data.is_empty() {
+         check_length(
+            &|| "tile layer".into(),
+            self.width,
+            self.height,
+            self.data.len(),
+         )?;
+      }
+      for chunk in &self.chunks {
+         check_length(
+            &|| format!("chunk at ({}, {})", chunk.x, chunk.y),
+            chunk.width,
+            chunk.height,
+            chunk.data.len(),
+         )?;
+      }
+      Ok(())
+   }
+
+   /// Iterates over the positions and GIDs of all of the layer's tiles, including empty ones.
+   pub fn tiles(&self) -> impl Iterator<Item = ((i32, i32), Gid)> + '_ {
+      // Finite layers are treated like a single big chunk.
+      let finite_layer = (0, 0, self.width, &self.data[..]);
+      self
+         .chunks
+         .iter()
+         .map(|chunk| (chunk.x, chunk.y, chunk.width, &chunk.data[..]))
+         .chain(std::iter::once(finite_layer))
+         .flat_map(|(x, y, width, data)| {
+            data.iter().enumerate().map(move |(i, &gid)| {
+               let position = (
+                  x + (i % width as usize) as i32,
+                  y + (i / width as usize) as i32,
+               );
+               (position, gid)
+            })
+         })
+   }
+}
+
+impl TryFrom<EncodedTileLayer> for TileLayer {
+   type Error = anyhow::Error;
+
+   fn try_from(layer: EncodedTileLayer) -> Result<Self, Self::Error> {
+      let encoding = layer.encoding.as_deref().unwrap_or("csv");
+      let compression = &layer.compression;
+      let layer = Self {
+         chunks: layer
+            .chunks
+            .into_iter()
+            .map(|chunk| {
+               Ok(Chunk {
+                  data: chunk.data.decode(encoding, compression)?,
+                  x: chunk.x,
+                  y: chunk.y,
+                  width: chunk.width,
+                  height: chunk.height,
+               })
+            })
+            .collect::<anyhow::Result<_>>()?,
+         data: layer
+            .data
+            .map(|data| data.decode(encoding, compression))
+            .transpose()?
+            .unwrap_or_default(),
+         width: layer.width,
+         height: layer.height,
+      };
+      layer.validate()?;
+      Ok(layer)
+   }
+}
+
+/// A point of a polygon or polyline, as stored in a JSON map.
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct EncodedPoint {
+   x: f32,
+   y: f32,
+}
+
+/// The shape of an object, as stored in a JSON map. Shapes other than rectangles are marked
+/// with a key named after the shape.
+#[derive(Debug, Clone, Deserialize)]
+struct EncodedShape {
+   #[serde(default)]
+   ellipse: bool,
+   #[serde(default)]
+   point: bool,
+   polygon: Option<Vec<EncodedPoint>>,
+   polyline: Option<Vec<EncodedPoint>>,
+}
+
+/// The shape of an object.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ObjectShape {
+   /// A rectangle spanning the object's width and height.
+   #[default]
+   Rectangle,
+   /// An ellipse inscribed in the object's rectangle.
+   Ellipse,
+   /// A single point at the object's position.
+   Point,
+   /// A closed polygon. The points are relative to the object's position.
+   Polygon(Vec<Vec2<f32>>),
+   /// An open polyline. The points are relative to the object's position.
+   Polyline(Vec<Vec2<f32>>),
+}
+
+impl ObjectShape {
+   /// Returns the shape with its points scaled by the given factor.
+   pub fn scaled(self, scale: Vec2<f32>) -> Self {
+      let scale_points = |points: Vec<Vec2<f32>>| points.into_iter().map(|p| p * scale).collect();
+      match self {
+         Self::Polygon(points) => Self::Polygon(scale_points(points)),
+         Self::Polyline(points) => Self::Polyline(scale_points(points)),
+         other => other,
+      }
+   }
+}
+
+impl EncodedShape {
+   /// Decodes the shape. Returns `None` if the object isn't marked with any shape, which means
+   /// it's a rectangle, unless it's an instance of a template with a different shape.
+   fn decode(self) -> Option<ObjectShape> {
+      let points = |points: Vec<EncodedPoint>| {
+         points.into_iter().map(|EncodedPoint { x, y }| Vec2::new(x, y)).collect()
+      };
+      if let Some(polygon) = self.polygon {
+         Some(ObjectShape::Polygon(points(polygon)))
+      } else if let Some(polyline) = self.polyline {
+         Some(ObjectShape::Polyline(points(polyline)))
+      } else if self.ellipse {
+         Some(ObjectShape::Ellipse)
+      } else if self.point {
+         Some(ObjectShape::Point)
+      } else {
+         None
+      }
+   }
+}
+
+/// Deserializes the shape of an object from the keys marking it.
+fn deserialize_shape<'de, D>(deserializer: D) -> Result<Option<ObjectShape>, D::Error>
+where
+   D: serde::Deserializer<'de>,
+{
+   EncodedShape::deserialize(deserializer).map(EncodedShape::decode)
+}
+
+/// An object in an object layer.
+///
+/// The fields an instance of a template can leave out are `None` if they're missing from the
+/// map, such that they can be told apart from values that were set explicitly. Use the accessor
+/// methods to read them with their defaults applied.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Object {
+   #[serde(default)]
+   pub id: ObjectId,
+   pub name: Option<String>,
+   #[serde(default)]
+   pub x: f32,
+   #[serde(default)]
+   pub y: f32,
+   pub width: Option<f32>,
+   pub height: Option<f32>,
+   pub rotation: Option<f32>,
+   /// The kind of object, as specified in the editor. Tiled 1.9 calls this the object's class.
+   #[serde(rename = "type", alias = "class")]
+   pub kind: Option<String>,
+   #[serde(default)]
+   pub properties: Properties,
+   /// The object's shape. `None` means the object is a rectangle.
+   #[serde(flatten, deserialize_with = "deserialize_shape")]
+   pub shape: Option<ObjectShape>,
+
+   /// If `Some`, the object is a text object.
+   pub text: Option<Text>,
+
+   /// The path to the template this object is an instance of, relative to the map. This is
+   /// `None` once the map's templates have been resolved.
+   #[serde(default)]
+   pub template: Option<String>,
+}
+
+impl Object {
+   /// Returns the object's name, which is empty if the object doesn't have one.
+   pub fn name(&self) -> &str {
+      self.name.as_deref().unwrap_or("")
+   }
+
+   /// Returns the object's kind, which is empty if the object doesn't have one.
+   pub fn kind(&self) -> &str {
+      self.kind.as_deref().unwrap_or("")
+   }
+
+   /// Returns the object's size. Missing dimensions are zero.
+   pub fn size(&self) -> Vec2<f32> {
+      Vec2::new(self.width.unwrap_or(0.0), self.height.unwrap_or(0.0))
+   }
+
+   /// Returns the object's rotation, in degrees.
+   pub fn rotation(&self) -> f32 {
+      self.rotation.unwrap_or(0.0)
+   }
+
+   /// Applies a template to the object. Anything the instance doesn't set is taken from the
+   /// template; properties are merged, with the instance's properties taking precedence.
+   fn apply_template(&mut self, template: &Object) {
+      if self.name.is_none() {
+         self.name = template.name.clone();
+      }
+      self.width = self.width.or(template.width);
+      self.height = self.height.or(template.height);
+      self.rotation = self.rotation.or(template.rotation);
+      if self.kind.is_none() {
+         self.kind = template.kind.clone();
+      }
+      if self.shape.is_none() {
+         self.shape = template.shape.clone();
+      }
+      for (name, value) in template.properties.iter() {
+         self.properties.0.entry(name.clone()).or_insert_with(|| value.clone());
+      }
+      if self.text.is_none() {
+         self.text = template.text.clone();
+      }
+      self.template = None;
+   }
+}
+
+/// An object template, as stored in a JSON template file.
+#[derive(Debug, Clone, Deserialize)]
+struct Template {
+   object: Object,
+}
+
+/// A cache of loaded object templates, such that each template file is only loaded once.
+struct Templates {
+   directory: PathBuf,
+   cache: HashMap<PathBuf, Object>,
+}
+
+impl Templates {
+   /// Creates a new template cache, resolving template paths relative to the given directory.
+   fn new(directory: &Path) -> Self {
+      Self {
+         directory: directory.to_owned(),
+         cache: HashMap::new(),
+      }
+   }
+
+   /// Returns the template at the given path, loading it if it hasn't been loaded yet.
+   /// XML templates (`.tx`) and JSON templates (`.tj`, `.json`) are supported.
+   fn get(&mut self, path: &str) -> anyhow::Result<&Object> {
+      let path = self.directory.join(path);
+      if !self.cache.contains_key(&path) {
+         let template = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tx") => xml::load_template_from_tx(&path)?,
+            Some("tj" | "json") => {
+               let json = std::fs::read_to_string(&path)
+                  .with_context(|| format!("cannot read {:?}", path))?;
+               serde_json::from_str::<Template>(&json)
+                  .with_context(|| format!("invalid template {:?}", path))?
+                  .object
+            }
+            _ => anyhow::bail!("template {:?} has an unknown file format", path),
+         };
+         self.cache.insert(path.clone(), template);
+      }
+      Ok(&self.cache[&path])
+   }
+}
+
+/// The horizontal alignment of text inside a text object.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextHAlign {
+   Left,
+   Center,
+   Right,
+}
+
+impl Default for TextHAlign {
+   fn default() -> Self {
+      Self::Left
+   }
+}
+
+impl FromStr for TextHAlign {
+   type Err = serde::de::value::Error;
+
+   fn from_str(s: &str) -> Result<Self, Self::Err> {
+      Self::deserialize(s.into_deserializer())
+   }
+}
+
+/// A text object.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Text {
+   #[serde(rename = "fontfamily")]
+   pub font_family: String,
+   #[serde(rename = "halign", default)]
+   pub h_align: TextHAlign,
+   #[serde(rename = "pixelsize")]
+   pub pixel_size: u32,
+   pub text: String,
+}
+
+/// An image layer.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImageLayer {
+   /// The path to the image, relative to the map. Once the map is loaded, the path is relative to
+   /// the working directory instead. Empty if the layer has no image.
+   #[serde(default)]
+   pub image: PathBuf,
+   /// Whether the image is repeated along the X axis.
+   #[serde(rename = "repeatx", default)]
+   pub repeat_x: bool,
+   /// Whether the image is repeated along the Y axis.
+   #[serde(rename = "repeaty", default)]
+   pub repeat_y: bool,
+}
+
+/// The kind of a layer.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum LayerKind {
+   #[serde(rename = "tilelayer")]
+   Tile(TileLayer),
+   #[serde(rename = "objectgroup")]
+   Object { objects: Vec<Object> },
+   #[serde(rename = "imagelayer")]
+   Image(ImageLayer),
+   /// A group of layers. Groups are flattened into their children when the map is loaded.
+   #[serde(rename = "group")]
+   Group { layers: Vec<Layer> },
+}
+
+/// A layer.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Layer {
+   #[serde(default)]
+   pub name: String,
+   #[serde(default = "Layer::default_visible")]
+   pub visible: bool,
+   #[serde(default = "Layer::default_opacity")]
+   pub opacity: f32,
+   /// The offset the layer is drawn at, in pixels.
+   #[serde(rename = "offsetx", default)]
+   pub offset_x: f32,
+   #[serde(rename = "offsety", default)]
+   pub offset_y: f32,
+   /// The speed the layer scrolls at relative to the camera. 1 scrolls along with the camera,
+   /// 0 makes the layer stay in place on the screen.
+   #[serde(rename = "parallaxx", default = "Layer::default_parallax")]
+   pub parallax_x: f32,
+   #[serde(rename = "parallaxy", default = "Layer::default_parallax")]
+   pub parallax_y: f32,
+   #[serde(default)]
+   pub properties: Properties,
+   #[serde(flatten)]
+   pub kind: LayerKind,
+}
+
+impl Layer {
+   /// Replaces group layers with their children. The children inherit the group's visibility,
+   /// opacity, offset and parallax factors.
+   fn flatten(layers: Vec<Layer>, flattened: &mut Vec<Layer>) {
+      for mut layer in layers {
+         if let LayerKind::Group { layers: children } = &mut layer.kind {
+            let mut children = std::mem::take(children);
+            for child in &mut children {
+               child.visible &= layer.visible;
+               child.opacity *= layer.opacity;
+               child.offset_x += layer.offset_x;
+               child.offset_y += layer.offset_y;
+               child.parallax_x *= layer.parallax_x;
+               child.parallax_y *= layer.parallax_y;
+            }
+            Self::flatten(children, flattened);
+         } else {
+            flattened.push(layer);
+         }
+      }
+   }
+
+   fn default_visible() -> bool {
+      true
+   }
+
+   fn default_opacity() -> f32 {
+      1.0
+   }
+
+   fn default_parallax() -> f32 {
+      1.0
+   }
+}
+
+/// A map, as stored in a JSON file. Its tilesets have not been loaded yet.
+#[derive(Debug, Clone, Deserialize)]
+struct EncodedMap {
+   #[serde(rename = "tilewidth")]
+   tile_width: u32,
+   #[serde(rename = "tileheight")]
+   tile_height: u32,
+   layers: Vec<Layer>,
+   #[serde(default)]
+   tilesets: Vec<TilesetRef>,
+   #[serde(default)]
+   properties: Properties,
+}
+
+/// A Tiled map.
+#[derive(Debug, Clone)]
+pub struct Map {
+   /// The size of the map's tiles, in pixels.
+   pub tile_width: u32,
+   pub tile_height: u32,
+   /// The map's layers, from bottom to top. Group layers are flattened into their children, so
+   /// none of the layers are groups.
+   pub layers: Vec<Layer>,
+   /// The tilesets used by the map, in the order they're declared in.
+   pub tilesets: Vec<MapTileset>,
+   /// The map's custom properties.
+   pub properties: Properties,
+   /// The paths to the template files used by the map's objects. The templates have already been
+   /// applied to the objects, so this is only useful for knowing which files the map depends on.
+   pub templates: Vec<PathBuf>,
+}
+
+impl Map {
+   /// Loads a map from JSON data. Object templates and external tilesets are resolved relative to
+   /// `directory`, which should be the directory the map was loaded from.
+   pub fn load_from_json(json: &str, directory: &Path) -> anyhow::Result<Self> {
+      let map: EncodedMap = serde_json::from_str(json)?;
+      Self::resolve(map, directory)
+   }
+
+   /// Loads a map from a file, picking the format based on the file's extension. XML maps (`.tmx`)
+   /// and JSON maps (`.tmj`, `.json`) are supported.
+   pub fn load(path: &Path) -> anyhow::Result<Self> {
+      match path.extension().and_then(|extension| extension.to_str()) {
+         Some("tmx") => Self::load_from_tmx(path),
+         Some("tmj" | "json") => {
+            let json =
+               std::fs::read_to_string(path).with_context(|| format!("cannot read {:?}", path))?;
+            Self::load_from_json(&json, path.parent().unwrap_or_else(|| Path::new("")))
+               .with_context(|| format!("in {:?}", path))
+         }
+         _ => anyhow::bail!("map {:?} has an unknown file format", path),
+      }
+   }
+
+   /// Creates a map from its encoded form, loading the tilesets and resolving templates and images
+   /// relative to `directory`.
+   fn resolve(map: EncodedMap, directory: &Path) -> anyhow::Result<Self> {
+      let tilesets = map
+         .tilesets
+         .into_iter()
+         .map(|tileset| tileset.load(directory))
+         .collect::<anyhow::Result<_>>()?;
+      let mut flattened = Vec::new();
+      Layer::flatten(map.layers, &mut flattened);
+      let mut map = Self {
+         tile_width: map.tile_width,
+         tile_height: map.tile_height,
+         layers: flattened,
+         tilesets,
+         properties: map.properties,
+         templates: Vec::new(),
+      };
+      map.resolve_templates(directory)?;
+      map.resolve_images(directory);
+      Ok(map)
+   }
+
+   /// Makes the paths to the images of image layers relative to the working directory.
+   fn resolve_images(&mut self, directory: &Path) {
+      for layer in &mut self.layers {
+         if let LayerKind::Image(image) = &mut layer.kind {
+            if !image.image.as_os_str().is_empty() {
+               image.image = directory.join(&image.image);
+            }
+         }
+      }
+   }
+
+   /// Applies templates to all objects that are instances of templates.
+   fn resolve_templates(&mut self, directory: &Path) -> anyhow::Result<()> {
+      let mut templates = Templates::new(directory);
+      for layer in &mut self.layers {
+         if let LayerKind::Object { objects } = &mut layer.kind {
+            for object in objects {
+               if let Some(path) = object.template.clone() {
+                  let template = templates
+                     .get(&path)
+                     .with_context(|| format!("object {}: cannot load template", object.id))?;
+                  object.apply_template(template);
+               }
+            }
+         }
+      }
+      self.templates = templates.cache.into_keys().collect();
+      self.templates.sort();
+      Ok(())
+   }
+}
@@ -0,0 +1,379 @@
+//! Loader for Tiled's native XML formats (`.tmx`, `.tsx`, `.tx`).
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use roxmltree::{Document, Node};
+use vek::Vec2;
+
+use super::{
+   encoding, Chunk, EncodedMap, Gid, ImageLayer, Layer, LayerKind, Map, Object, ObjectShape,
+   Properties, PropertyValue, Text, TileLayer, Tileset, TilesetRef,
+};
+
+/// Returns the parsed value of an attribute, or `None` if the attribute is not present.
+fn attribute<T>(node: Node, name: &str) -> anyhow::Result<Option<T>>
+where
+   T: FromStr,
+   T::Err: Display,
+{
+   node
+      .attribute(name)
+      .map(|value| {
+         value.parse().map_err(|error| {
+            anyhow::anyhow!(
+               "<{}> attribute '{}' has invalid value {:?}: {}",
+               node.tag_name().name(),
+               name,
+               value,
+               error
+            )
+         })
+      })
+      .transpose()
+}
+
+/// Returns the parsed value of an attribute, erroring out if the attribute is not present.
+fn required_attribute<T>(node: Node, name: &str) -> anyhow::Result<T>
+where
+   T: FromStr,
+   T::Err: Display,
+{
+   attribute(node, name)?.ok_or_else(|| {
+      anyhow::anyhow!(
+         "<{}> is missing the '{}' attribute",
+         node.tag_name().name(),
+         name
+      )
+   })
+}
+
+/// Iterates over the child elements of a node with the given tag name.
+fn children<'a, 'input: 'a>(
+   node: Node<'a, 'input>,
+   tag_name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+   node.children().filter(move |child| child.has_tag_name(tag_name))
+}
+
+/// Reads and parses an XML file, and passes its root element to the provided function.
+fn with_document<T>(
+   path: &Path,
+   root_tag_name: &str,
+   parse: impl FnOnce(Node) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+   let xml = std::fs::read_to_string(path).with_context(|| format!("cannot read {:?}", path))?;
+   let document = Document::parse(&xml).with_context(|| format!("{:?} is not valid XML", path))?;
+   let root = document.root_element();
+   if !root.has_tag_name(root_tag_name) {
+      anyhow::bail!(
+         "{:?}: expected a <{}> root element, but got <{}>",
+         path,
+         root_tag_name,
+         root.tag_name().name()
+      );
+   }
+   parse(root).with_context(|| format!("in {:?}", path))
+}
+
+/// Namespace struct for XML parsing functions.
+struct Parser;
+
+impl Parser {
+   /// Parses a `<properties>` element into `properties`, overwriting any existing properties with
+   /// the same name.
+   fn parse_properties(node: Node, properties: &mut Properties) -> anyhow::Result<()> {
+      for property in children(node, "property") {
+         let name: String = required_attribute(property, "name")?;
+         let kind = property.attribute("type").unwrap_or("string");
+         let value = if kind == "class" {
+            // Unlike in JSON files, class members are stored along with their types, so they can
+            // be parsed just like any other properties.
+            let mut members = Properties::default();
+            Self::parse_child_properties(property, &mut members)?;
+            Ok(PropertyValue::Class(members))
+         } else {
+            // Multiline string properties are stored in the element's text rather than the
+            // `value` attribute.
+            let value = property.attribute("value").or_else(|| property.text()).unwrap_or("");
+            PropertyValue::parse(kind, property.attribute("propertytype"), value)
+         };
+         let value = value.with_context(|| format!("in property {:?}", name))?;
+         properties.0.insert(name, value);
+      }
+      Ok(())
+   }
+
+   /// Parses the `<properties>` child of the given node, if it has one.
+   fn parse_child_properties(node: Node, properties: &mut Properties) -> anyhow::Result<()> {
+      for child in children(node, "properties") {
+         Self::parse_properties(child, properties)?;
+      }
+      Ok(())
+   }
+
+   /// Parses the type of an object or tile, which Tiled 1.9 renamed to `class`.
+   fn parse_class(node: Node) -> anyhow::Result<Option<String>> {
+      Ok(attribute(node, "type")?.or(attribute(node, "class")?))
+   }
+
+   /// Parses a text object's `<text>` element.
+   fn parse_text(node: Node) -> anyhow::Result<Text> {
+      Ok(Text {
+         font_family: attribute(node, "fontfamily")?.unwrap_or_else(|| "sans-serif".into()),
+         h_align: attribute(node, "halign")?.unwrap_or_default(),
+         pixel_size: attribute(node, "pixelsize")?.unwrap_or(16),
+         text: node.text().unwrap_or("").to_owned(),
+      })
+   }
+
+   /// Parses the points of a `<polygon>` or `<polyline>` element.
+   fn parse_points(node: Node) -> anyhow::Result<Vec<Vec2<f32>>> {
+      let points: String = required_attribute(node, "points")?;
+      points
+         .split_whitespace()
+         .map(|point| {
+            let (x, y) =
+               point.split_once(',').ok_or_else(|| anyhow::anyhow!("invalid point {:?}", point))?;
+            let coordinate = |c: &str| {
+               c.parse::<f32>().with_context(|| format!("invalid coordinate in point {:?}", point))
+            };
+            Ok(Vec2::new(coordinate(x)?, coordinate(y)?))
+         })
+         .collect()
+   }
+
+   /// Parses the shape of an object from its children. Objects without a shape element are
+   /// rectangles, unless they're instances of a template, so their shape is `None`.
+   fn parse_shape(node: Node) -> anyhow::Result<Option<ObjectShape>> {
+      for child in node.children().filter(|child| child.is_element()) {
+         match child.tag_name().name() {
+            "ellipse" => return Ok(Some(ObjectShape::Ellipse)),
+            "point" => return Ok(Some(ObjectShape::Point)),
+            "polygon" => return Ok(Some(ObjectShape::Polygon(Self::parse_points(child)?))),
+            "polyline" => return Ok(Some(ObjectShape::Polyline(Self::parse_points(child)?))),
+            _ => (),
+         }
+      }
+      Ok(None)
+   }
+
+   /// Parses an `<object>` element. Templates are not applied here; instead the path to the
+   /// object's template is stored in the object, and resolved once the whole map is loaded.
+   fn parse_object(node: Node) -> anyhow::Result<Object> {
+      let mut properties = Properties::default();
+      Self::parse_child_properties(node, &mut properties)?;
+      Ok(Object {
+         id: attribute(node, "id")?.unwrap_or(0),
+         name: attribute(node, "name")?,
+         x: attribute(node, "x")?.unwrap_or(0.0),
+         y: attribute(node, "y")?.unwrap_or(0.0),
+         width: attribute(node, "width")?,
+         height: attribute(node, "height")?,
+         rotation: attribute(node, "rotation")?,
+         kind: Self::parse_class(node)?,
+         properties,
+         shape: Self::parse_shape(node)?,
+         text: children(node, "text").next().map(Self::parse_text).transpose()?,
+         template: attribute(node, "template")?,
+      })
+   }
+
+   /// Parses an `<objectgroup>` element.
+   fn parse_object_group(node: Node) -> anyhow::Result<LayerKind> {
+      let objects = children(node, "object")
+         .map(|object| {
+            Self::parse_object(object).with_context(|| {
+               format!("in object {}", object.attribute("id").unwrap_or("<no id>"))
+            })
+         })
+         .collect::<Result<_, _>>()?;
+      Ok(LayerKind::Object { objects })
+   }
+
+   /// Parses tile data stored in an element, using the encoding and compression specified in
+   /// the layer's `<data>` element.
+   fn parse_tiles(data: Node, node: Node) -> anyhow::Result<Vec<Gid>> {
+      match data.attribute("encoding") {
+         Some(encoding) => encoding::decode_tiles(
+            node.text().unwrap_or(""),
+            encoding,
+            data.attribute("compression").unwrap_or(""),
+         ),
+         // Without an encoding, tiles are stored as <tile> elements.
+         None => children(node, "tile")
+            .map(|tile| Ok(attribute(tile, "gid")?.unwrap_or(Gid(0))))
+            .collect(),
+      }
+   }
+
+   /// Parses a `<layer>` element. Layers of infinite maps store their tiles in chunks, while
+   /// layers of finite maps store them directly inside the `<data>` element.
+   fn parse_tile_layer(node: Node, infinite: bool) -> anyhow::Result<LayerKind> {
+      let data = children(node, "data")
+         .next()
+         .ok_or_else(|| anyhow::anyhow!("tile layer does not have any data"))?;
+      let chunks: Vec<_> = children(data, "chunk")
+         .map(|chunk| {
+            Ok(Chunk {
+               data: Self::parse_tiles(data, chunk)?,
+               x: required_attribute(chunk, "x")?,
+               y: required_attribute(chunk, "y")?,
+               width: required_attribute(chunk, "width")?,
+               height: required_attribute(chunk, "height")?,
+            })
+         })
+         .collect::<anyhow::Result<_>>()?;
+      let tiles = if infinite {
+         Vec::new()
+      } else {
+         Self::parse_tiles(data, data)?
+      };
+      let layer = TileLayer {
+         chunks,
+         data: tiles,
+         width: required_attribute(node, "width")?,
+         height: required_attribute(node, "height")?,
+      };
+      layer.validate()?;
+      Ok(LayerKind::Tile(layer))
+   }
+
+   /// Parses an `<imagelayer>` element.
+   fn parse_image_layer(node: Node) -> anyhow::Result<LayerKind> {
+      let image = match children(node, "image").next() {
+         Some(image) => attribute(image, "source")?.unwrap_or_default(),
+         None => PathBuf::new(),
+      };
+      Ok(LayerKind::Image(ImageLayer {
+         image,
+         repeat_x: attribute::<u8>(node, "repeatx")?.is_some_and(|repeat| repeat != 0),
+         repeat_y: attribute::<u8>(node, "repeaty")?.is_some_and(|repeat| repeat != 0),
+      }))
+   }
+
+   /// Parses all layers in a map or group. `infinite` tells whether the map is infinite.
+   fn parse_layers(node: Node, infinite: bool) -> anyhow::Result<Vec<Layer>> {
+      node
+         .children()
+         .filter(|child| child.is_element())
+         .filter_map(|child| {
+            let kind = match child.tag_name().name() {
+               "layer" => Self::parse_tile_layer(child, infinite),
+               "objectgroup" => Self::parse_object_group(child),
+               "imagelayer" => Self::parse_image_layer(child),
+               "group" => {
+                  Self::parse_layers(child, infinite).map(|layers| LayerKind::Group { layers })
+               }
+               _ => return None,
+            };
+            Some(
+               kind.and_then(|kind| Self::parse_layer(child, kind)).with_context(|| {
+                  format!(
+                     "in layer {:?}",
+                     child.attribute("name").unwrap_or("<unnamed>")
+                  )
+               }),
+            )
+         })
+         .collect()
+   }
+
+   /// Parses the attributes and properties shared by all kinds of layers.
+   fn parse_layer(node: Node, kind: LayerKind) -> anyhow::Result<Layer> {
+      let mut properties = Properties::default();
+      Self::parse_child_properties(node, &mut properties)?;
+      Ok(Layer {
+         name: attribute(node, "name")?.unwrap_or_default(),
+         // Booleans are stored as 0 or 1 in XML maps.
+         visible: attribute::<u8>(node, "visible")? != Some(0),
+         opacity: attribute(node, "opacity")?.unwrap_or(1.0),
+         offset_x: attribute(node, "offsetx")?.unwrap_or(0.0),
+         offset_y: attribute(node, "offsety")?.unwrap_or(0.0),
+         parallax_x: attribute(node, "parallaxx")?.unwrap_or(1.0),
+         parallax_y: attribute(node, "parallaxy")?.unwrap_or(1.0),
+         properties,
+         kind,
+      })
+   }
+
+   /// Parses a map's `<tileset>` element, which either references an external tileset or
+   /// embeds one.
+   fn parse_tileset_ref(node: Node) -> anyhow::Result<TilesetRef> {
+      let first_gid = required_attribute(node, "firstgid")?;
+      Ok(match attribute(node, "source")? {
+         Some(source) => TilesetRef::External { first_gid, source },
+         None => TilesetRef::Embedded {
+            first_gid,
+            tileset: Self::parse_tileset(node)?,
+         },
+      })
+   }
+
+   /// Parses a `<tileset>` element.
+   fn parse_tileset(node: Node) -> anyhow::Result<Tileset> {
+      let tiles = children(node, "tile")
+         .map(|tile| {
+            let id = required_attribute(tile, "id")?;
+            let mut properties = Properties::default();
+            Self::parse_child_properties(tile, &mut properties)?;
+            Ok(super::Tile {
+               id,
+               kind: Self::parse_class(tile)?.unwrap_or_default(),
+               object_group: children(tile, "objectgroup")
+                  .next()
+                  .map(Self::parse_object_group)
+                  .transpose()?,
+               properties,
+            })
+         })
+         .collect::<anyhow::Result<_>>()?;
+      Ok(Tileset {
+         tile_width: required_attribute(node, "tilewidth")?,
+         tile_height: required_attribute(node, "tileheight")?,
+         tile_count: required_attribute(node, "tilecount")?,
+         tiles,
+      })
+   }
+}
+
+/// Loads the object stored in a template (`.tx`) file.
+pub(super) fn load_template_from_tx(path: &Path) -> anyhow::Result<Object> {
+   with_document(path, "template", |root| {
+      let object = children(root, "object")
+         .next()
+         .ok_or_else(|| anyhow::anyhow!("template does not contain an object"))?;
+      Parser::parse_object(object)
+   })
+}
+
+impl Tileset {
+   /// Loads a tileset from a Tiled XML tileset (`.tsx`) file.
+   pub fn load_from_tsx(path: &Path) -> anyhow::Result<Self> {
+      with_document(path, "tileset", Parser::parse_tileset)
+   }
+}
+
+impl Map {
+   /// Loads a map from a Tiled XML map (`.tmx`) file. Object templates and external tilesets are
+   /// resolved relative to the map's directory.
+   pub fn load_from_tmx(path: &Path) -> anyhow::Result<Self> {
+      with_document(path, "map", |root| {
+         let mut properties = Properties::default();
+         Parser::parse_child_properties(root, &mut properties)?;
+         let infinite = attribute::<u8>(root, "infinite")?.is_some_and(|infinite| infinite != 0);
+         let map = EncodedMap {
+            tile_width: required_attribute(root, "tilewidth")?,
+            tile_height: required_attribute(root, "tileheight")?,
+            layers: Parser::parse_layers(root, infinite)?,
+            tilesets: children(root, "tileset")
+               .map(Parser::parse_tileset_ref)
+               .collect::<anyhow::Result<_>>()?,
+            properties,
+         };
+         Map::resolve(map, path.parent().unwrap_or_else(|| Path::new("")))
+      })
+   }
+}
@@ -0,0 +1,54 @@
+//! Watching files for changes.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Watches a set of files for changes, by periodically polling their modification times.
+pub struct FileWatcher {
+   files: Vec<(PathBuf, Option<SystemTime>)>,
+   last_poll: Instant,
+}
+
+impl FileWatcher {
+   /// How often the files are polled.
+   const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+   /// Creates a new watcher for the given files.
+   pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Self {
+      Self {
+         files: files
+            .into_iter()
+            .map(|path| {
+               let modified = Self::modified(&path);
+               (path, modified)
+            })
+            .collect(),
+         last_poll: Instant::now(),
+      }
+   }
+
+   /// Returns whether any of the files was modified since the last time this returned `true`, or
+   /// since the watcher was created. Files that can't be read are treated as modified once they
+   /// can be read again.
+   pub fn changed(&mut self) -> bool {
+      if self.last_poll.elapsed() < Self::POLL_INTERVAL {
+         return false;
+      }
+      self.last_poll = Instant::now();
+
+      let mut changed = false;
+      for (path, last_modified) in &mut self.files {
+         let modified = Self::modified(path);
+         if modified != *last_modified {
+            *last_modified = modified;
+            changed |= modified.is_some();
+         }
+      }
+      changed
+   }
+
+   /// Returns the modification time of a file, or `None` if it can't be read.
+   fn modified(path: &PathBuf) -> Option<SystemTime> {
+      std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+   }
+}
@@ -0,0 +1,24 @@
+//! Checks and compiles the map the game ships with, the same way the asset compiler does.
+
+use memphlus::map::{CompiledMap, Map};
+
+#[test]
+fn shipped_map_has_no_errors() {
+   let diagnostics = Map::check(&Map::source_path()).unwrap();
+   let errors: Vec<String> = diagnostics
+      .iter()
+      .filter(|diagnostic| diagnostic.is_error())
+      .map(|diagnostic| diagnostic.to_string())
+      .collect();
+   assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn shipped_map_compiles() {
+   let map = CompiledMap::compile(&Map::source_path()).unwrap();
+   let output = std::env::temp_dir().join("memphlus-test-map.bin");
+   map.write(&output).unwrap();
+   let read = CompiledMap::read(&output);
+   let _ = std::fs::remove_file(&output);
+   assert_eq!(read.unwrap().sources(), map.sources());
+}